@@ -0,0 +1,117 @@
+//! Line-by-line rendering of the timer screen.
+//!
+//! Each frame is drawn at fixed rows from the top-left corner and wrapped to
+//! the current terminal width, so the layout stays put when the window is
+//! resized instead of depending on where the previous frame left the cursor.
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{ContentStyle, Print, PrintStyledContent, StyledContent},
+    terminal::{Clear, ClearType},
+};
+use std::io::{self, Write};
+
+#[derive(Default, PartialEq)]
+pub struct Frame {
+    lines: Vec<(String, ContentStyle)>,
+}
+
+impl Frame {
+    pub fn line(&mut self, text: impl Into<String>) {
+        self.styled(text, ContentStyle::new());
+    }
+
+    /// A line drawn in `style`, e.g. colored by the current phase.
+    pub fn styled(&mut self, text: impl Into<String>, style: ContentStyle) {
+        self.lines.push((text.into(), style));
+    }
+
+    /// Draws the frame, clearing the whole screen first when `full` is set
+    /// (after a resize) and otherwise only the rows it covers.
+    pub fn draw(&self, out: &mut impl Write, columns: u16, full: bool) -> io::Result<()> {
+        if full {
+            queue!(out, Clear(ClearType::All))?;
+        }
+        let rows = self.lines.iter().flat_map(|(text, style)| {
+            wrap(text, columns)
+                .into_iter()
+                .map(move |row| (row, *style))
+        });
+        let mut count = 0;
+        for (row, (text, style)) in rows.enumerate() {
+            queue!(out, MoveTo(0, row as u16), Clear(ClearType::CurrentLine))?;
+            // Styled rows reset their colors at the end, so nothing bleeds
+            // into the next row or frame
+            if style == ContentStyle::new() {
+                queue!(out, Print(text))?;
+            } else {
+                queue!(out, PrintStyledContent(StyledContent::new(style, text)))?;
+            }
+            count = row + 1;
+        }
+        // Anything below is left over from a taller previous frame
+        queue!(
+            out,
+            MoveTo(0, count as u16),
+            Clear(ClearType::FromCursorDown)
+        )?;
+        out.flush()
+    }
+}
+
+/// Cells a character takes up; emoji are drawn double width.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x2300..=0x23FF | 0x2600..=0x27BF | 0x1F000.. => 2,
+        _ => 1,
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Splits `text` into rows no wider than `columns`, breaking at spaces where
+/// possible. An empty line stays one empty row.
+fn wrap(text: &str, columns: u16) -> Vec<String> {
+    let columns = usize::from(columns).max(1);
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    for word in text.split(' ') {
+        let needed = if row.is_empty() { 0 } else { 1 } + text_width(word);
+        if !row.is_empty() && text_width(&row) + needed > columns {
+            rows.push(std::mem::take(&mut row));
+        }
+        if !row.is_empty() {
+            row.push(' ');
+        }
+        for c in word.chars() {
+            if !row.is_empty() && text_width(&row) + char_width(c) > columns {
+                rows.push(std::mem::take(&mut row));
+            }
+            row.push(c);
+        }
+    }
+    rows.push(row);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("", 10), vec![""]);
+        assert_eq!(wrap("24:13 🍅", 10), vec!["24:13 🍅"]);
+        assert_eq!(
+            wrap("Press 'p' to pause, 'q' to quit", 12),
+            vec!["Press 'p' to", "pause, 'q'", "to quit"]
+        );
+        // Words wider than the terminal are split
+        assert_eq!(wrap("[██████████] 50%", 8), vec!["[███████", "███] 50%"]);
+        // Emoji count double so they don't push past the edge
+        assert_eq!(wrap("🍅🍅🍅", 4), vec!["🍅🍅", "🍅"]);
+    }
+}
@@ -0,0 +1,888 @@
+//! Log of completed work sessions, kept as newline-delimited JSON next to
+//! the state file and shown by `pomo log` and `pomo stats`.
+
+use crate::{local_day, local_utc_offset, sibling_path, SessionTag, TimerPhase, TimerState};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SessionRecord {
+    /// When the session ended, in Unix seconds.
+    pub timestamp: u64,
+    pub phase: TimerPhase,
+    pub duration_seconds: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<SessionTag>,
+    /// Distractions marked with 'i' during the session.
+    #[serde(default)]
+    pub interruptions: u32,
+}
+
+impl SessionRecord {
+    /// Record of the session `state` is in, ending at `now`.
+    pub fn new(state: &TimerState, now: u64) -> Self {
+        Self {
+            timestamp: now,
+            phase: state.phase,
+            duration_seconds: state.elapsed_seconds(),
+            label: state.label.clone(),
+            tag: state.tag,
+            interruptions: state.interruptions,
+        }
+    }
+}
+
+pub fn get_history_path(state_path: &Path) -> PathBuf {
+    sibling_path(state_path, "-history.jsonl")
+}
+
+/// Appends one record to the log, creating the file on first use.
+pub fn append(path: &Path, record: &SessionRecord) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Reads every record in the log. A missing log is empty, and lines that
+/// don't parse (say, from an interrupted write) are skipped.
+pub fn load(path: &Path) -> Result<Vec<SessionRecord>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Prints every logged session followed by today's focus total.
+pub fn print_log(path: &Path, now: u64) -> Result<()> {
+    let records = load(path)?;
+    if records.is_empty() {
+        println!("No sessions logged yet.");
+        return Ok(());
+    }
+    for record in &records {
+        println!("{}", format_record(record));
+    }
+    let [today, _, _] = summarize(&records, local_day(now), local_day);
+    println!();
+    println!(
+        "Today: {} sessions, {} min focused",
+        today.sessions,
+        today.seconds / 60
+    );
+    Ok(())
+}
+
+/// Prints the sessions of local `day` in order, with the breaks between
+/// them, followed by the day's focus total.
+pub fn print_replay(path: &Path, day: i64) -> Result<()> {
+    let records = load(path)?;
+    let entries = timeline(&records, day, local_day);
+    let date = format_date(day);
+    if entries.is_empty() {
+        println!("No sessions on {}.", date);
+        return Ok(());
+    }
+    println!("{}:", date);
+    for entry in &entries {
+        println!("{}", format_entry(entry, local_utc_offset(entry.start)));
+    }
+    let sessions: Vec<_> = entries.iter().filter_map(|entry| entry.session).collect();
+    println!();
+    println!(
+        "{} sessions, {} min focused",
+        sessions.len(),
+        sessions
+            .iter()
+            .map(|record| u64::from(record.duration_seconds))
+            .sum::<u64>()
+            / 60
+    );
+    Ok(())
+}
+
+/// Day index of a "YYYY-MM-DD" date, for use as a clap value parser.
+pub fn parse_date(value: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid date (expected YYYY-MM-DD): {}", value);
+    let mut parts = value.splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<u32>().ok());
+    let (year, month, day) = match (next(), next(), next()) {
+        (Some(year), Some(month), Some(day)) => (i64::from(year), month, day),
+        _ => return Err(invalid()),
+    };
+    let days = days_from_civil(year, month, day);
+    // Out-of-range days like 02-30 come back as a different date
+    if !(1..=12).contains(&month) || civil_from_days(days) != (year, month, day) {
+        return Err(invalid());
+    }
+    Ok(days)
+}
+
+/// Start of the window given to `pomo stats --since`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Since {
+    /// From this day on, counted from 1970-01-01.
+    Date(i64),
+    /// The last this many days, today included.
+    Days(u32),
+}
+
+impl Since {
+    /// First local day of the window, given today's.
+    fn first_day(self, today: i64) -> i64 {
+        match self {
+            Since::Date(day) => day,
+            Since::Days(days) => today - i64::from(days) + 1,
+        }
+    }
+}
+
+/// Parses a "YYYY-MM-DD" date or a number of days like "7d", for use as a
+/// clap value parser.
+pub fn parse_since(value: &str) -> Result<Since, String> {
+    if let Some(days) = value.strip_suffix('d') {
+        return match days.parse() {
+            Ok(days) if days > 0 => Ok(Since::Days(days)),
+            _ => Err(format!(
+                "invalid --since (expected YYYY-MM-DD or a number of days like 7d): {}",
+                value
+            )),
+        };
+    }
+    parse_date(value).map(Since::Date)
+}
+
+/// The records from `first_day` on.
+fn filter_since(
+    records: Vec<SessionRecord>,
+    first_day: i64,
+    day_of: impl Fn(u64) -> i64,
+) -> Vec<SessionRecord> {
+    records
+        .into_iter()
+        .filter(|record| day_of(record.timestamp) >= first_day)
+        .collect()
+}
+
+/// Prints a small table of sessions and focus time for today, this week
+/// and all time, followed by the focus time spent on each task and, with
+/// `by_type`, on each kind of session. With `since`, the totals are for
+/// that window instead.
+pub fn print_stats(path: &Path, now: u64, by_type: bool, since: Option<Since>) -> Result<()> {
+    let mut records = load(path)?;
+    if records.is_empty() {
+        println!("No sessions yet - finish a pomodoro and check back!");
+        return Ok(());
+    }
+    if let Some(since) = since {
+        let first_day = since.first_day(local_day(now));
+        records = filter_since(records, first_day, local_day);
+        let date = format_date(first_day);
+        if records.is_empty() {
+            println!("No sessions since {}.", date);
+            return Ok(());
+        }
+        let [_, _, total] = summarize(&records, local_day(now), local_day);
+        println!(
+            "Since {}: {} sessions, {} focused",
+            date,
+            total.sessions,
+            format_focus(total.seconds)
+        );
+    } else {
+        for row in summary_table(&records, now) {
+            println!("{}", row);
+        }
+        let [this_week, last_week] = compare_weeks(&records, local_day(now), local_day);
+        println!("{}", format_week_comparison(this_week, last_week));
+    }
+    let tasks = by_task(&records);
+    if !tasks.is_empty() {
+        println!();
+        println!("{:<30} {:>8} {:>9}", "Task", "Sessions", "Focus");
+        for (label, totals) in tasks {
+            println!(
+                "{:<30} {:>8} {:>9}",
+                label,
+                totals.sessions,
+                format_focus(totals.seconds)
+            );
+        }
+    }
+    if by_type {
+        println!();
+        println!("{:<10} {:>8} {:>9}", "Type", "Sessions", "Focus");
+        for (name, totals) in by_tag(&records) {
+            println!(
+                "{:<10} {:>8} {:>9}",
+                name,
+                totals.sessions,
+                format_focus(totals.seconds)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// All-time work totals for deep, light and untagged sessions.
+fn by_tag(records: &[SessionRecord]) -> [(&'static str, Totals); 3] {
+    let mut totals = [
+        (SessionTag::Deep.name(), Totals::default()),
+        (SessionTag::Light.name(), Totals::default()),
+        ("untagged", Totals::default()),
+    ];
+    for record in records
+        .iter()
+        .filter(|record| record.phase == TimerPhase::Work)
+    {
+        let index = match record.tag {
+            Some(SessionTag::Deep) => 0,
+            Some(SessionTag::Light) => 1,
+            None => 2,
+        };
+        totals[index].1.sessions += 1;
+        totals[index].1.seconds += u64::from(record.duration_seconds);
+    }
+    totals
+}
+
+/// All-time work totals per task label, most focused first. Sessions
+/// without a label are left out.
+fn by_task(records: &[SessionRecord]) -> Vec<(&str, Totals)> {
+    let mut tasks: Vec<(&str, Totals)> = Vec::new();
+    for record in records
+        .iter()
+        .filter(|record| record.phase == TimerPhase::Work)
+    {
+        let Some(label) = record.label.as_deref() else {
+            continue;
+        };
+        let index = match tasks.iter().position(|(name, _)| *name == label) {
+            Some(index) => index,
+            None => {
+                tasks.push((label, Totals::default()));
+                tasks.len() - 1
+            }
+        };
+        tasks[index].1.sessions += 1;
+        tasks[index].1.seconds += u64::from(record.duration_seconds);
+    }
+    tasks.sort_by_key(|(_, totals)| std::cmp::Reverse(totals.seconds));
+    tasks
+}
+
+/// Rows of the today/this week/total table, as shown by `pomo stats` and
+/// the timer's stats overlay.
+pub fn summary_table(records: &[SessionRecord], now: u64) -> Vec<String> {
+    let totals = summarize(records, local_day(now), local_day);
+    let mut rows = vec![format!("{:<10} {:>8} {:>9}", "", "Sessions", "Focus")];
+    for (name, totals) in ["Today", "This week", "Total"].iter().zip(totals) {
+        rows.push(format!(
+            "{:<10} {:>8} {:>9}",
+            name,
+            totals.sessions,
+            format_focus(totals.seconds)
+        ));
+    }
+    rows
+}
+
+/// Shortest gap between two sessions that the replay shows as a break.
+const MIN_BREAK_SECONDS: u64 = 60;
+
+/// A stretch of a replayed day: a logged session, or the break inferred
+/// from the gap before the next one when `session` is `None`.
+#[derive(PartialEq, Debug)]
+struct TimelineEntry<'a> {
+    start: u64,
+    end: u64,
+    session: Option<&'a SessionRecord>,
+}
+
+/// Work sessions that ended on `day`, oldest first, with the breaks between
+/// them. Sessions are logged when they end, so each one started its
+/// duration before its timestamp.
+fn timeline(
+    records: &[SessionRecord],
+    day: i64,
+    day_of: impl Fn(u64) -> i64,
+) -> Vec<TimelineEntry<'_>> {
+    let mut sessions: Vec<_> = records
+        .iter()
+        .filter(|record| record.phase == TimerPhase::Work && day_of(record.timestamp) == day)
+        .collect();
+    sessions.sort_by_key(|record| record.timestamp);
+    let mut entries: Vec<TimelineEntry> = Vec::new();
+    for record in sessions {
+        let start = record
+            .timestamp
+            .saturating_sub(u64::from(record.duration_seconds));
+        if let Some(previous) = entries.last() {
+            if start >= previous.end + MIN_BREAK_SECONDS {
+                entries.push(TimelineEntry {
+                    start: previous.end,
+                    end: start,
+                    session: None,
+                });
+            }
+        }
+        entries.push(TimelineEntry {
+            start,
+            end: record.timestamp,
+            session: Some(record),
+        });
+    }
+    entries
+}
+
+/// Replay line like "09:00-09:25   25 min  Work  write report".
+fn format_entry(entry: &TimelineEntry, utc_offset_secs: i64) -> String {
+    let span = format!(
+        "{}-{}",
+        format_clock(entry.start, utc_offset_secs),
+        format_clock(entry.end, utc_offset_secs)
+    );
+    match entry.session {
+        Some(record) => format!("{}  {}", span, format_session(record)),
+        None => format!("{}  {:>3} min  Break", span, (entry.end - entry.start) / 60),
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+struct Totals {
+    sessions: u32,
+    seconds: u64,
+}
+
+/// Work totals for `today`, the week containing it (starting Monday) and
+/// all time. `day_of` maps a timestamp to its local day index, so sessions
+/// land on the day they happened wherever the user is.
+fn summarize(records: &[SessionRecord], today: i64, day_of: impl Fn(u64) -> i64) -> [Totals; 3] {
+    let week_start = week_start(today);
+    let mut totals = [Totals::default(); 3];
+    for record in records
+        .iter()
+        .filter(|record| record.phase == TimerPhase::Work)
+    {
+        let day = day_of(record.timestamp);
+        let buckets = [day == today, (week_start..=today).contains(&day), true];
+        for (total, _) in totals.iter_mut().zip(buckets).filter(|(_, hit)| *hit) {
+            total.sessions += 1;
+            total.seconds += u64::from(record.duration_seconds);
+        }
+    }
+    totals
+}
+
+/// Monday of the week containing `day`.
+fn week_start(day: i64) -> i64 {
+    // Day 0, 1970-01-01, was a Thursday
+    day - (day + 3).rem_euclid(7)
+}
+
+/// Work totals for the week containing `today` (so far) and the whole week
+/// before it.
+fn compare_weeks(
+    records: &[SessionRecord],
+    today: i64,
+    day_of: impl Fn(u64) -> i64,
+) -> [Totals; 2] {
+    let this_week = week_start(today);
+    let mut totals = [Totals::default(); 2];
+    for record in records
+        .iter()
+        .filter(|record| record.phase == TimerPhase::Work)
+    {
+        let day = day_of(record.timestamp);
+        let index = if (this_week..=today).contains(&day) {
+            0
+        } else if (this_week - 7..this_week).contains(&day) {
+            1
+        } else {
+            continue;
+        };
+        totals[index].sessions += 1;
+        totals[index].seconds += u64::from(record.duration_seconds);
+    }
+    totals
+}
+
+/// Line like "vs last week: +2 sessions (+25%), +50m focus (+25%)".
+fn format_week_comparison(this_week: Totals, last_week: Totals) -> String {
+    if last_week.sessions == 0 {
+        return "vs last week: no sessions last week to compare with yet".to_string();
+    }
+    let percent = |this: u64, last: u64| {
+        if last == 0 {
+            return String::new();
+        }
+        let change = (this as f64 - last as f64) * 100.0 / last as f64;
+        format!(" ({:+}%)", change.round() as i64)
+    };
+    let focus = if this_week.seconds >= last_week.seconds {
+        format!("+{}", format_focus(this_week.seconds - last_week.seconds))
+    } else {
+        format!("-{}", format_focus(last_week.seconds - this_week.seconds))
+    };
+    format!(
+        "vs last week: {:+} sessions{}, {} focus{}",
+        i64::from(this_week.sessions) - i64::from(last_week.sessions),
+        percent(this_week.sessions.into(), last_week.sessions.into()),
+        focus,
+        percent(this_week.seconds, last_week.seconds)
+    )
+}
+
+/// Focus time like "1h 05m", or "25m" under an hour.
+pub fn format_focus(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// One log line like "2024-03-09 14:05  25 min  Work  write report", with the
+/// tag and any interruptions after the phase.
+fn format_record(record: &SessionRecord) -> String {
+    format!(
+        "{}  {}",
+        format_local_time(record.timestamp, local_utc_offset(record.timestamp)),
+        format_session(record)
+    )
+}
+
+/// The part of a log line after the time, like "25 min  Work  write report".
+fn format_session(record: &SessionRecord) -> String {
+    let mut line = format!(
+        "{:>3} min  {:?}",
+        record.duration_seconds / 60,
+        record.phase
+    );
+    if let Some(tag) = record.tag {
+        line.push_str(&format!(" ({})", tag.name()));
+    }
+    if record.interruptions > 0 {
+        line.push_str(&format!(", {} interruptions", record.interruptions));
+    }
+    if let Some(label) = &record.label {
+        line.push_str("  ");
+        line.push_str(label);
+    }
+    line
+}
+
+/// Local date and time like "2024-03-09 14:05".
+fn format_local_time(epoch: u64, utc_offset_secs: i64) -> String {
+    let local = epoch as i64 + utc_offset_secs;
+    let (year, month, day) = civil_from_days(local.div_euclid(24 * 60 * 60));
+    let seconds = local.rem_euclid(24 * 60 * 60);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// ISO-8601 local timestamp like "2024-03-09T14:05:09+01:00".
+pub fn format_iso8601(epoch: u64, utc_offset_secs: i64) -> String {
+    let local = epoch as i64 + utc_offset_secs;
+    let offset = utc_offset_secs.unsigned_abs() / 60;
+    format!(
+        "{}T{}:{:02}{}{:02}:{:02}",
+        format_date(local.div_euclid(24 * 60 * 60)),
+        format_clock(epoch, utc_offset_secs),
+        local.rem_euclid(60),
+        if utc_offset_secs < 0 { '-' } else { '+' },
+        offset / 60,
+        offset % 60
+    )
+}
+
+/// Local time of day like "14:05".
+fn format_clock(epoch: u64, utc_offset_secs: i64) -> String {
+    format_local_time(epoch, utc_offset_secs)[11..].to_string()
+}
+
+/// Date like "2024-03-09" of a day counted from 1970-01-01.
+fn format_date(day: i64) -> String {
+    let (year, month, day) = civil_from_days(day);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Day counted from 1970-01-01 of a calendar date; the inverse of
+/// `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Calendar date of a day counted from 1970-01-01, using Howard Hinnant's
+/// `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "pomo-history-test-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ))
+    }
+
+    /// A work session of `minutes` ending at `timestamp`, without a label,
+    /// tag or interruptions.
+    fn work_record(timestamp: u64, minutes: u32) -> SessionRecord {
+        SessionRecord {
+            timestamp,
+            phase: TimerPhase::Work,
+            duration_seconds: minutes * 60,
+            label: None,
+            tag: None,
+            interruptions: 0,
+        }
+    }
+
+    #[test]
+    fn test_append_and_load() {
+        let path = history_path("append");
+        let _ = fs::remove_file(&path);
+        assert!(load(&path).unwrap().is_empty());
+
+        let record = SessionRecord {
+            label: Some("write report".to_string()),
+            tag: Some(SessionTag::Deep),
+            interruptions: 2,
+            ..work_record(1_700_000_000, 25)
+        };
+        append(&path, &record).unwrap();
+        append(&path, &record).unwrap();
+        fs::write(
+            &path,
+            fs::read_to_string(&path).unwrap() + "{\"timestamp\":\n",
+        )
+        .unwrap();
+
+        assert_eq!(load(&path).unwrap(), vec![record.clone(), record.clone()]);
+        let _ = fs::remove_file(&path);
+
+        assert!(
+            format_record(&record).ends_with(" min  Work (deep), 2 interruptions  write report")
+        );
+        // Records logged before interruptions were saved read as none
+        let legacy: SessionRecord =
+            serde_json::from_str(r#"{"timestamp":0,"phase":"Work","duration_seconds":60}"#)
+                .unwrap();
+        assert_eq!(legacy.interruptions, 0);
+    }
+
+    #[test]
+    fn test_summarize_buckets_by_local_day() {
+        // Sessions at 23:30 on Wednesday 2024-03-13 and earlier days in
+        // UTC-5, when it's already the next day in UTC
+        let offset = -5 * 3600;
+        let wednesday = 1_710_390_600;
+        let day = 24 * 60 * 60;
+        let records = [
+            work_record(wednesday, 25),
+            work_record(wednesday - day, 25),
+            work_record(wednesday - 2 * day, 50),
+            // The Sunday before belongs to last week
+            work_record(wednesday - 3 * day, 25),
+        ];
+        let day_of = |timestamp| crate::day_index(timestamp, offset);
+        let today = day_of(wednesday);
+
+        let [today, week, total] = summarize(&records, today, day_of);
+        assert_eq!(
+            today,
+            Totals {
+                sessions: 1,
+                seconds: 25 * 60
+            }
+        );
+        assert_eq!(
+            week,
+            Totals {
+                sessions: 3,
+                seconds: 100 * 60
+            }
+        );
+        assert_eq!(
+            total,
+            Totals {
+                sessions: 4,
+                seconds: 125 * 60
+            }
+        );
+
+        assert_eq!(format_focus(25 * 60), "25m");
+        assert_eq!(format_focus(125 * 60), "2h 05m");
+    }
+
+    #[test]
+    fn test_compare_weeks() {
+        // Wednesday 2024-03-13 in UTC
+        let wednesday = 1_710_340_000;
+        let day = 24 * 60 * 60;
+        let day_of = |timestamp| crate::day_index(timestamp, 0);
+        let today = day_of(wednesday);
+        let this_week = [
+            work_record(wednesday, 25),
+            work_record(wednesday - day, 25),
+            work_record(wednesday - 2 * day, 50),
+        ];
+        let last_week = [
+            // Sunday and Monday of last week
+            work_record(wednesday - 3 * day, 25),
+            work_record(wednesday - 9 * day, 25),
+            work_record(wednesday - 9 * day, 25),
+            work_record(wednesday - 9 * day, 25),
+            // Two weeks ago
+            work_record(wednesday - 14 * day, 25),
+        ];
+        let records: Vec<_> = this_week.iter().chain(&last_week).cloned().collect();
+
+        let [this, last] = compare_weeks(&records, today, day_of);
+        assert_eq!(
+            [this, last],
+            [
+                Totals {
+                    sessions: 3,
+                    seconds: 100 * 60
+                },
+                Totals {
+                    sessions: 4,
+                    seconds: 100 * 60
+                }
+            ]
+        );
+        assert_eq!(
+            format_week_comparison(this, last),
+            "vs last week: -1 sessions (-25%), +0m focus (+0%)"
+        );
+        assert_eq!(
+            format_week_comparison(
+                last,
+                Totals {
+                    sessions: 2,
+                    seconds: 40 * 60
+                }
+            ),
+            "vs last week: +2 sessions (+100%), +1h 00m focus (+150%)"
+        );
+
+        // The first week has nothing to compare with
+        let [this, last] = compare_weeks(&this_week, today, day_of);
+        assert_eq!(last, Totals::default());
+        assert_eq!(
+            format_week_comparison(this, last),
+            "vs last week: no sessions last week to compare with yet"
+        );
+    }
+
+    #[test]
+    fn test_timeline() {
+        // 2024-03-13 in UTC
+        let day = parse_date("2024-03-13").unwrap();
+        let at = |hour: u64, minute: u64| day as u64 * 24 * 60 * 60 + hour * 3600 + minute * 60;
+        let labeled = |end, label: &str| SessionRecord {
+            label: Some(label.to_string()),
+            ..work_record(end, 25)
+        };
+        let records = [
+            // Logged out of order, and a session from the day before
+            labeled(at(10, 0), "review"),
+            labeled(at(9, 25), "write report"),
+            work_record(at(9, 0) - 24 * 60 * 60, 25),
+            // Back to back with the one before: no break in between
+            work_record(at(10, 25), 25),
+        ];
+        let day_of = |timestamp| crate::day_index(timestamp, 0);
+
+        let lines: Vec<_> = timeline(&records, day, day_of)
+            .iter()
+            .map(|entry| format_entry(entry, 0))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "09:00-09:25   25 min  Work  write report",
+                "09:25-09:35   10 min  Break",
+                "09:35-10:00   25 min  Work  review",
+                "10:00-10:25   25 min  Work",
+            ]
+        );
+        assert!(timeline(&records, day + 1, day_of).is_empty());
+
+        assert_eq!(format_date(day), "2024-03-13");
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_date("2024-02-29"), Ok(day - 13));
+        for invalid in ["2023-02-29", "2024-13-01", "2024-03", "yesterday"] {
+            assert!(parse_date(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_since() {
+        let today = parse_date("2024-03-13").unwrap();
+        assert_eq!(
+            parse_since("2024-01-01"),
+            Ok(Since::Date(parse_date("2024-01-01").unwrap()))
+        );
+        assert_eq!(parse_since("7d"), Ok(Since::Days(7)));
+        for invalid in ["0d", "-1d", "d", "7w", "2024-01-32"] {
+            assert!(parse_since(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(Since::Days(7).first_day(today), today - 6);
+        assert_eq!(Since::Days(1).first_day(today), today);
+        assert_eq!(Since::Date(today - 30).first_day(today), today - 30);
+
+        let at_noon = |day: i64| work_record(day as u64 * 24 * 60 * 60 + 12 * 3600, 25);
+        let records = vec![at_noon(today - 7), at_noon(today - 6), at_noon(today)];
+        let day_of = |timestamp| crate::day_index(timestamp, 0);
+        let window = filter_since(records.clone(), Since::Days(7).first_day(today), day_of);
+        assert_eq!(window, records[1..]);
+        assert!(filter_since(records, today + 1, day_of).is_empty());
+    }
+
+    #[test]
+    fn test_summary_table() {
+        let now = 1_710_390_600;
+        let records = [
+            work_record(now, 25),
+            work_record(now - 60, 25),
+            work_record(now - 30 * 24 * 60 * 60, 25),
+        ];
+        assert_eq!(
+            summary_table(&records, now),
+            vec![
+                "           Sessions     Focus",
+                "Today             2       50m",
+                "This week         2       50m",
+                "Total             3    1h 15m",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_by_task() {
+        let labeled = |minutes, label: &str| SessionRecord {
+            label: Some(label.to_string()),
+            ..work_record(0, minutes)
+        };
+        let records = [
+            labeled(25, "email"),
+            labeled(50, "write report"),
+            SessionRecord {
+                phase: TimerPhase::Break,
+                ..labeled(5, "email")
+            },
+            work_record(0, 25),
+            labeled(15, "email"),
+        ];
+        assert_eq!(
+            by_task(&records),
+            vec![
+                (
+                    "write report",
+                    Totals {
+                        sessions: 1,
+                        seconds: 50 * 60
+                    }
+                ),
+                (
+                    "email",
+                    Totals {
+                        sessions: 2,
+                        seconds: 40 * 60
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_by_tag() {
+        let tagged = |minutes, tag| SessionRecord {
+            tag: Some(tag),
+            ..work_record(0, minutes)
+        };
+        let records = [
+            tagged(50, SessionTag::Deep),
+            tagged(25, SessionTag::Deep),
+            tagged(25, SessionTag::Light),
+            work_record(0, 10),
+            SessionRecord {
+                phase: TimerPhase::Break,
+                ..tagged(5, SessionTag::Light)
+            },
+        ];
+        let totals = |sessions, minutes: u64| Totals {
+            sessions,
+            seconds: minutes * 60,
+        };
+        assert_eq!(
+            by_tag(&records),
+            [
+                ("deep", totals(2, 75)),
+                ("light", totals(1, 25)),
+                ("untagged", totals(1, 10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_local_time() {
+        assert_eq!(format_local_time(0, 0), "1970-01-01 00:00");
+        assert_eq!(format_local_time(1_709_993_100, 0), "2024-03-09 14:05");
+        assert_eq!(
+            format_local_time(1_709_993_100, 9 * 3600),
+            "2024-03-09 23:05"
+        );
+        // Ten hours ahead of UTC it's already the next day
+        assert_eq!(
+            format_local_time(1_709_993_100, 10 * 3600),
+            "2024-03-10 00:05"
+        );
+        assert_eq!(format_local_time(951_782_400, 0), "2000-02-29 00:00");
+    }
+}
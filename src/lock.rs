@@ -0,0 +1,152 @@
+//! Single-instance lock, so two interactive timers don't overwrite each
+//! other's state. The lock file sits next to the state file and holds the
+//! owner's PID; a lock left behind by a process that no longer exists is
+//! taken over. A lock without a readable PID is never taken over, since its
+//! owner may not have written the PID yet.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// How long to give a just-started owner to write its PID.
+const PID_WRITE_GRACE: Duration = Duration::from_millis(200);
+
+pub fn get_lock_path(state_path: &Path) -> PathBuf {
+    crate::sibling_path(state_path, ".lock")
+}
+
+/// Held for as long as the timer runs; dropping it removes the lock file,
+/// including while unwinding from a panic.
+pub struct InstanceLock {
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let pid = std::process::id();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        // One retry is enough: the only reason to retry is a stale lock
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", pid)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    return Ok(Self {
+                        path: path.to_path_buf(),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let owner = read_pid(path).or_else(|| {
+                        thread::sleep(PID_WRITE_GRACE);
+                        read_pid(path)
+                    });
+                    match owner {
+                        Some(owner) if owner != pid && is_running(owner) => {
+                            bail!("pomo is already running (pid {})", owner)
+                        }
+                        Some(_) => {
+                            let _ = fs::remove_file(path);
+                        }
+                        None => bail!(
+                            "{} exists but holds no PID; remove it if pomo isn't running",
+                            path.display()
+                        ),
+                    }
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to create {}", path.display()))
+                }
+            }
+        }
+        bail!("failed to take the lock {}", path.display())
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Leave the file alone if another instance has since taken it over
+        if read_pid(&self.path) == Some(std::process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks whether the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // The process exists but belongs to someone else
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a portable liveness check, assume the owner is still running.
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "pomo-lock-test-{}-{}.lock",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_lock_holds_pid_until_dropped() {
+        let path = lock_path("exclusive");
+        let _ = fs::remove_file(&path);
+
+        let lock = InstanceLock::acquire(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_refuses_live_or_unwritten_owner_and_takes_over_dead_one() {
+        let path = lock_path("owners");
+
+        // PID 1 is always running
+        fs::write(&path, "1\n").unwrap();
+        let error = InstanceLock::acquire(&path).err().unwrap();
+        assert_eq!(error.to_string(), "pomo is already running (pid 1)");
+        assert_eq!(read_pid(&path), Some(1));
+
+        // A lock whose owner hasn't written its PID yet is left alone
+        fs::write(&path, "").unwrap();
+        let error = InstanceLock::acquire(&path).err().unwrap();
+        assert!(error.to_string().contains("holds no PID"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        // A dead owner's lock is taken over
+        fs::write(&path, format!("{}\n", i32::MAX)).unwrap();
+        let lock = InstanceLock::acquire(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+        drop(lock);
+        assert!(!path.exists());
+    }
+}
@@ -1,30 +1,410 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
 use crossterm::{
-    cursor,
     event::{self, Event, KeyCode},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    style::Print,
+    terminal::{self, disable_raw_mode, enable_raw_mode, SetTitle},
 };
-use notify_rust::Notification;
+use notifier::{
+    availability_warning, default_message, render_template, set_summary, BellNotifier,
+    DesktopNotifier, NotificationAction, Notifier, TransitionEvent,
+};
+use notify_rust::Timeout;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs,
-    io::{self, Write},
-    path::PathBuf,
-    time::{Duration, Instant},
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{mpsc, Arc},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::time;
 
+mod frame;
+mod history;
+mod lock;
+mod notifier;
+#[cfg(feature = "power")]
+mod power;
+#[cfg(all(unix, feature = "rpc"))]
+mod rpc;
+mod schedule;
+mod theme;
+
+use frame::Frame;
+use history::{get_history_path, SessionRecord};
+use schedule::{Schedule, ScheduleProgress};
+use theme::Theme;
+
 #[derive(Parser)]
 #[command(name = "pomo")]
 #[command(about = "A simple Pomodoro timer")]
-struct Cli {}
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Work session length in minutes; remembered across restarts
+    #[arg(long, value_name = "MINUTES", value_parser = phase_minutes())]
+    work: Option<u32>,
+
+    /// Long break length in minutes; remembered across restarts
+    #[arg(long, value_name = "MINUTES", value_parser = phase_minutes())]
+    long_break: Option<u32>,
+
+    /// Take a long break after this many work sessions; remembered across restarts
+    #[arg(long, value_name = "SESSIONS", value_parser = clap::value_parser!(u32).range(1..))]
+    long_break_every: Option<u32>,
+
+    /// What you're focusing on, shown under the timer and saved with each session
+    #[arg(long, value_name = "LABEL")]
+    task: Option<String>,
+
+    /// State file to use instead of the default; related files are kept beside it
+    #[arg(long, global = true, value_name = "PATH", env = "POMO_STATE_FILE")]
+    state_file: Option<PathBuf>,
+
+    /// Maximum net minutes a burst of rapid +/- presses can add or remove
+    #[arg(long, value_name = "MINUTES", default_value_t = 5)]
+    max_adjust: u32,
+
+    /// Break length in minutes, or a range like 5-8 to pick a random length for each break; remembered across restarts
+    #[arg(long = "break", value_name = "MINUTES")]
+    break_range: Option<BreakRange>,
+
+    /// Shorter warm-up work duration used for the first session of each day
+    #[arg(long, value_name = "MINUTES", value_parser = phase_minutes())]
+    warmup: Option<u32>,
+
+    /// How often the display updates when idle; below 1000 wakes more often, above it redraws every few seconds to save battery
+    #[arg(long, value_name = "MS", default_value_t = 1000, value_parser = parse_refresh)]
+    refresh: u64,
+
+    /// Move the progress bar in steps of this many percent, so it updates less often
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u32).range(1..=50))]
+    progress_step: Option<u32>,
+
+    /// Lock the screen when a break begins so you actually step away
+    #[arg(long)]
+    lock_on_break: bool,
+
+    /// Notification body when a work session ends; supports {today_count}, {streak} and {next_phase}
+    #[arg(long, value_name = "TEMPLATE")]
+    work_message: Option<String>,
+
+    /// Notification body when a break ends; supports the same placeholders as --work-message
+    #[arg(long, value_name = "TEMPLATE")]
+    break_message: Option<String>,
+
+    /// How long desktop notifications stay up: milliseconds, "never" or "default"
+    #[arg(long, value_name = "MS", default_value = "default")]
+    notify_timeout: Timeout,
+
+    /// Don't show desktop notifications
+    #[arg(long)]
+    no_notify: bool,
+
+    /// Ring the terminal bell on every phase change
+    #[arg(long)]
+    bell: bool,
+
+    /// Earn XP for completed pomodoros and show a level bar
+    #[arg(long)]
+    gamify: bool,
+
+    /// Send a congratulatory notification summing up each completed set when its long break is due
+    #[arg(long)]
+    celebrate: bool,
+
+    /// Save the unused time of skipped breaks, to add to a later break with 'b'
+    #[arg(long)]
+    bank_breaks: bool,
+
+    /// Show the remaining time in the terminal/tab title, like "🍅 24:13 - pomo"
+    #[arg(long)]
+    set_title: bool,
+
+    /// Title style, implying --set-title: exact ("🍅 24:13 - pomo") or rounded ("(25) pomo")
+    #[arg(long, value_name = "FORMAT")]
+    title_format: Option<TitleFormat>,
+
+    /// Require pressing 'q' twice in quick succession to quit
+    #[arg(long)]
+    double_quit: bool,
+
+    /// Align to a shared work/break schedule anchored at a Unix timestamp or a local HH:MM today
+    #[arg(long, value_name = "EPOCH|HH:MM", value_parser = parse_sync_anchor)]
+    sync: Option<u64>,
+
+    /// Commit to work sessions: no +/- adjustments or skipping until the break
+    #[arg(long)]
+    commit: bool,
+
+    /// Pause automatically on battery or when the lid closes (Linux)
+    #[cfg(feature = "power")]
+    #[arg(long)]
+    auto_pause_power: bool,
+
+    /// Hard limit on work sessions per day; new work sessions wait until tomorrow once reached
+    #[arg(long, value_name = "SESSIONS")]
+    daily_limit: Option<u32>,
+
+    /// Resume paused if the previous run didn't exit cleanly, instead of counting the downtime
+    #[arg(long)]
+    pause_after_crash: bool,
+
+    /// Seconds to show a finished phase before the next one starts
+    #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+    dwell: u64,
+
+    /// Wait for Space before starting the next phase instead of moving on automatically
+    #[arg(long)]
+    manual: bool,
+
+    /// Run without the full-screen display, printing a line per phase change; stop with Ctrl+C
+    #[arg(
+        long,
+        visible_alias = "no-tui",
+        conflicts_with_all = ["manual", "set_title", "title_format", "pause_after_crash"]
+    )]
+    quiet: bool,
+
+    /// Prefix each line printed under --quiet with an ISO-8601 local timestamp
+    #[arg(long, requires = "quiet")]
+    timestamps: bool,
+
+    /// Shell command to run whenever a work session starts
+    #[arg(long, value_name = "CMD")]
+    on_work: Option<String>,
+
+    /// Shell command to run whenever a break starts (and long breaks, unless --on-long-break is set)
+    #[arg(long, value_name = "CMD")]
+    on_break: Option<String>,
+
+    /// Shell command to run whenever a long break starts
+    #[arg(long, value_name = "CMD")]
+    on_long_break: Option<String>,
+}
+
+impl Cli {
+    /// State file from --state-file, then POMO_STATE_FILE, then the default.
+    fn state_path(&self) -> PathBuf {
+        self.state_file.clone().unwrap_or_else(get_config_path)
+    }
+
+    /// How to show the countdown in the terminal title, if at all.
+    fn title(&self) -> Option<TitleFormat> {
+        self.title_format
+            .or(self.set_title.then_some(TitleFormat::Exact))
+    }
+
+    /// Hook command to run on entering `phase`.
+    fn hook_for(&self, phase: TimerPhase) -> Option<&str> {
+        match phase {
+            TimerPhase::Work => self.on_work.as_deref(),
+            TimerPhase::Break => self.on_break.as_deref(),
+            TimerPhase::LongBreak => self.on_long_break.as_deref().or(self.on_break.as_deref()),
+            TimerPhase::Interruption => None,
+        }
+    }
+}
+
+/// xterm escapes that push and pop the window title, so the title from
+/// before pomo started comes back on quit. Terminals without a title stack
+/// ignore them.
+const SAVE_TITLE: &str = "\x1b[22;0t";
+const RESTORE_TITLE: &str = "\x1b[23;0t";
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Debug)]
+enum TitleFormat {
+    /// Emoji and exact countdown, e.g. "🍅 24:13 - pomo"
+    Exact,
+    /// Minutes rounded up like an unread count, e.g. "(25) pomo"
+    Rounded,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Run a fixed sequence of labeled work/break blocks from a file
+    Schedule {
+        /// Schedule file with one `<work|break> <minutes> [label]` block per line
+        file: PathBuf,
+    },
+    /// List completed work sessions and today's focus time
+    Log,
+    /// Summarize sessions and focus time for today, this week and all time
+    Stats {
+        /// Also break focus time down by session type (deep, light, untagged)
+        #[arg(long)]
+        by_type: bool,
+        /// Total only the sessions from this day (YYYY-MM-DD) or this many
+        /// days (like 7d, today included) on
+        #[arg(long, value_name = "DATE|Nd", value_parser = history::parse_since)]
+        since: Option<history::Since>,
+    },
+    /// Show one day's sessions as a timeline, with the breaks between them
+    Replay {
+        /// Day to replay, as YYYY-MM-DD
+        #[arg(value_parser = history::parse_date)]
+        date: i64,
+    },
+    /// Print the remaining time once, for status bars like tmux or polybar
+    Status {
+        /// Print a JSON snapshot instead of the formatted line
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Range accepted by `--refresh`, in milliseconds.
+const REFRESH_RANGE_MS: std::ops::RangeInclusive<u64> = 20..=60_000;
+
+fn parse_refresh(value: &str) -> Result<u64, String> {
+    let ms: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid refresh interval: {}", value))?;
+    Ok(ms.clamp(*REFRESH_RANGE_MS.start(), *REFRESH_RANGE_MS.end()))
+}
+
+fn parse_sync_anchor(value: &str) -> Result<u64, String> {
+    if let Ok(epoch) = value.parse::<u64>() {
+        return Ok(epoch);
+    }
+    let invalid = || {
+        format!(
+            "invalid sync anchor (expected epoch seconds or HH:MM): {}",
+            value
+        )
+    };
+    let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+    let hours: u64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u64 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    let now = unix_now();
+    Ok(local_clock_time(
+        now,
+        local_utc_offset(now),
+        hours * 3600 + minutes * 60,
+    ))
+}
+
+/// Epoch seconds of a wall-clock time (seconds past local midnight) on the same day as `now`.
+fn local_clock_time(now: u64, utc_offset_secs: i64, seconds_past_midnight: u64) -> u64 {
+    let midnight = day_index(now, utc_offset_secs) * 24 * 60 * 60 - utc_offset_secs;
+    (midnight + seconds_past_midnight as i64).max(0) as u64
+}
+
+/// Phase and remaining seconds at `now` on a repeating work/break schedule
+/// that started at `anchor`. Times before the anchor follow the same cycle.
+fn synced_position(
+    anchor: u64,
+    now: u64,
+    work_seconds: u32,
+    break_seconds: u32,
+) -> (TimerPhase, u32) {
+    let cycle = (work_seconds + break_seconds).max(1) as i64;
+    let position = (now as i64 - anchor as i64).rem_euclid(cycle) as u32;
+    if position < work_seconds {
+        (TimerPhase::Work, work_seconds - position)
+    } else {
+        (TimerPhase::Break, work_seconds + break_seconds - position)
+    }
+}
+
+/// Most break time that can be banked from skipped breaks.
+const MAX_BANKED_BREAK_SECONDS: u32 = 30 * 60;
+
+/// Adding time never takes a phase past this many seconds.
+const MAX_ADJUSTED_SECONDS: u32 = 4 * 60 * 60;
+
+/// Longest phase that can be configured, in minutes.
+const MAX_PHASE_MINUTES: u32 = MAX_ADJUSTED_SECONDS / 60;
+
+/// Parser for phase lengths given in minutes, bounded so converting them to
+/// seconds can't overflow.
+fn phase_minutes() -> clap::builder::RangedI64ValueParser<u32> {
+    clap::value_parser!(u32).range(1..=i64::from(MAX_PHASE_MINUTES))
+}
+
+/// Presses of +/- closer together than this are coalesced into one burst.
+const ADJUST_BURST_WINDOW: Duration = Duration::from_millis(1500);
+
+/// Break length bounds in minutes, inclusive. A fixed length has `min == max`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+struct BreakRange {
+    min: u32,
+    max: u32,
+}
+
+impl BreakRange {
+    /// Picks a break length in seconds within the range.
+    fn pick_seconds(&self, rng: &mut fastrand::Rng) -> u32 {
+        rng.u32(self.min..=self.max) * 60
+    }
+}
+
+impl std::str::FromStr for BreakRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parse = |minutes: &str| match minutes.trim().parse::<u32>() {
+            Ok(minutes) if (1..=MAX_PHASE_MINUTES).contains(&minutes) => Ok(minutes),
+            Ok(_) => Err(format!(
+                "break length {} is not in 1..={}",
+                value, MAX_PHASE_MINUTES
+            )),
+            Err(_) => Err(format!("invalid break length: {}", value)),
+        };
+        let (min, max) = match value.split_once('-') {
+            Some((min, max)) => (parse(min)?, parse(max)?),
+            None => {
+                let minutes = parse(value)?;
+                (minutes, minutes)
+            }
+        };
+        if min > max {
+            return Err(format!("break range {} is backwards", value));
+        }
+        Ok(Self { min, max })
+    }
+}
+
+/// Kind of focus a work session was, as tagged by the user.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+enum SessionTag {
+    Deep,
+    Light,
+}
+
+impl SessionTag {
+    fn name(&self) -> &'static str {
+        match self {
+            SessionTag::Deep => "deep",
+            SessionTag::Light => "light",
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
 enum TimerPhase {
     Work,
     Break,
+    LongBreak,
+    Interruption,
+}
+
+impl TimerPhase {
+    /// Name passed to phase hooks.
+    fn name(&self) -> &'static str {
+        match self {
+            TimerPhase::Work => "work",
+            TimerPhase::Break => "break",
+            TimerPhase::LongBreak => "long_break",
+            TimerPhase::Interruption => "interruption",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -33,6 +413,60 @@ struct TimerState {
     remaining_seconds: u32,
     is_paused: bool,
     last_update: Option<u64>,
+    /// Full length of the current phase, including any time added or removed.
+    #[serde(default = "TimerState::work_duration")]
+    phase_seconds: u32,
+    #[serde(default = "TimerState::work_duration")]
+    work_seconds: u32,
+    #[serde(default)]
+    warmup_seconds: Option<u32>,
+    #[serde(default)]
+    break_range: Option<BreakRange>,
+    #[serde(default = "TimerState::long_break_duration")]
+    long_break_seconds: u32,
+    #[serde(default = "default_long_break_every")]
+    long_break_every: u32,
+    #[serde(default)]
+    completed_work_sessions: u32,
+    #[serde(default)]
+    last_work_day: Option<i64>,
+    #[serde(default)]
+    completed_today: u32,
+    #[serde(default)]
+    completed_day: Option<i64>,
+    #[serde(default)]
+    streak_days: u32,
+    #[serde(default)]
+    xp: u32,
+    #[serde(default)]
+    interruptions: u32,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    tag: Option<SessionTag>,
+    #[serde(default = "default_clean_exit")]
+    clean_exit: bool,
+    #[serde(default)]
+    schedule: Option<ScheduleProgress>,
+    /// Whether the finished phase was already recorded and announced, so a
+    /// restart while it waits to move on doesn't do it twice.
+    #[serde(default)]
+    finish_announced: bool,
+    /// Unused time from skipped breaks under `--bank-breaks`.
+    #[serde(default)]
+    banked_break_seconds: u32,
+    /// Length chosen for the current short break, so restarting it with
+    /// `R` keeps a length picked from `--break` range.
+    #[serde(default)]
+    picked_break_seconds: Option<u32>,
+}
+
+fn default_clean_exit() -> bool {
+    true
+}
+
+fn default_long_break_every() -> u32 {
+    4
 }
 
 impl Default for TimerState {
@@ -42,6 +476,26 @@ impl Default for TimerState {
             remaining_seconds: 25 * 60, // 25 minutes
             is_paused: false,
             last_update: None,
+            phase_seconds: 25 * 60,
+            work_seconds: Self::work_duration(),
+            warmup_seconds: None,
+            break_range: None,
+            long_break_seconds: Self::long_break_duration(),
+            long_break_every: default_long_break_every(),
+            completed_work_sessions: 0,
+            last_work_day: None,
+            completed_today: 0,
+            completed_day: None,
+            streak_days: 0,
+            xp: 0,
+            interruptions: 0,
+            label: None,
+            tag: None,
+            clean_exit: true,
+            schedule: None,
+            finish_announced: false,
+            banked_break_seconds: 0,
+            picked_break_seconds: None,
         }
     }
 }
@@ -55,42 +509,215 @@ impl TimerState {
         5 * 60 // 5 minutes
     }
 
+    fn long_break_duration() -> u32 {
+        15 * 60 // 15 minutes
+    }
+
+    fn interruption_duration() -> u32 {
+        5 * 60 // 5 minutes
+    }
+
     fn reset_to_work(&mut self) {
+        self.reset_to_work_on(local_day(unix_now()));
+    }
+
+    /// Starts a work session on the given local day, using the warm-up
+    /// duration the first time a day's work session begins.
+    fn reset_to_work_on(&mut self, day: i64) {
+        let first_of_day = self.last_work_day != Some(day);
         self.phase = TimerPhase::Work;
-        self.remaining_seconds = Self::work_duration();
+        self.start_countdown(match self.warmup_seconds {
+            Some(warmup) if first_of_day => warmup,
+            _ => self.work_seconds,
+        });
+        self.is_paused = false;
+        self.last_update = None;
+        self.last_work_day = Some(day);
+        self.interruptions = 0;
+        self.tag = None;
+    }
+
+    /// Fixed length of a break in seconds, or None when breaks are random.
+    fn fixed_break_seconds(&self) -> Option<u32> {
+        match self.break_range {
+            Some(range) if range.min != range.max => None,
+            Some(range) => Some(range.min * 60),
+            None => Some(Self::break_duration()),
+        }
+    }
+
+    /// Applies durations passed on the command line. They're stored in the
+    /// state so later resets keep honoring them after a restart, and a phase
+    /// that hasn't started counting down yet picks up its new length.
+    fn configure_durations(&mut self, work_minutes: Option<u32>, break_range: Option<BreakRange>) {
+        let untouched = self.last_update.is_none();
+        if let Some(minutes) = work_minutes {
+            let seconds = minutes * 60;
+            if untouched
+                && self.phase == TimerPhase::Work
+                && self.remaining_seconds == self.work_seconds
+            {
+                self.start_countdown(seconds);
+            }
+            self.work_seconds = seconds;
+        }
+        if let Some(range) = break_range {
+            let was_fresh_break = untouched
+                && self.phase == TimerPhase::Break
+                && self.fixed_break_seconds() == Some(self.remaining_seconds);
+            self.break_range = Some(range);
+            if let Some(seconds) = self.fixed_break_seconds().filter(|_| was_fresh_break) {
+                self.start_countdown(seconds);
+                self.picked_break_seconds = Some(seconds);
+            }
+        }
+    }
+
+    /// Applies the long break settings passed on the command line, keeping
+    /// them in the state like `configure_durations` does.
+    fn configure_long_breaks(&mut self, every: Option<u32>, minutes: Option<u32>) {
+        if let Some(every) = every {
+            self.long_break_every = every;
+        }
+        if let Some(minutes) = minutes {
+            self.long_break_seconds = minutes * 60;
+        }
+    }
+
+    /// Whether enough work sessions are done for the next break to be long.
+    fn long_break_due(&self) -> bool {
+        self.completed_work_sessions >= self.long_break_every.max(1)
+    }
+
+    /// Starts whichever break follows a completed work session.
+    fn reset_to_next_break(&mut self) {
+        if self.long_break_due() {
+            self.reset_to_long_break();
+        } else {
+            self.reset_to_break();
+        }
+    }
+
+    /// Starts a long break, beginning a new cycle of work sessions.
+    fn reset_to_long_break(&mut self) {
+        self.phase = TimerPhase::LongBreak;
+        self.start_countdown(self.long_break_seconds);
         self.is_paused = false;
         self.last_update = None;
+        self.completed_work_sessions = 0;
     }
 
     fn reset_to_break(&mut self) {
+        self.reset_to_break_with(&mut fastrand::Rng::new());
+    }
+
+    /// Starts a break, picking its length from the configured range. The
+    /// chosen length is kept in `picked_break_seconds`, so it stays stable
+    /// across saves, restarts and `R`.
+    fn reset_to_break_with(&mut self, rng: &mut fastrand::Rng) {
         self.phase = TimerPhase::Break;
-        self.remaining_seconds = Self::break_duration();
+        let seconds = self.pick_break_seconds(rng);
+        self.picked_break_seconds = Some(seconds);
+        self.start_countdown(seconds);
+        self.is_paused = false;
+        self.last_update = None;
+    }
+
+    fn pick_break_seconds(&self, rng: &mut fastrand::Rng) -> u32 {
+        match self.break_range {
+            Some(range) => range.pick_seconds(rng),
+            None => Self::break_duration(),
+        }
+    }
+
+    /// Starts the current phase over from its full length, keeping it paused
+    /// or running as it was.
+    fn restart_phase(&mut self) {
+        self.start_countdown(match self.phase {
+            TimerPhase::Work => self.work_seconds,
+            TimerPhase::Break => self
+                .picked_break_seconds
+                .unwrap_or_else(|| self.pick_break_seconds(&mut fastrand::Rng::new())),
+            TimerPhase::LongBreak => self.long_break_seconds,
+            TimerPhase::Interruption => Self::interruption_duration(),
+        });
+        self.last_update = None;
+    }
+
+    /// Sets the countdown to a fresh phase of `seconds`.
+    fn start_countdown(&mut self, seconds: u32) {
+        self.remaining_seconds = seconds;
+        self.phase_seconds = seconds;
+        self.finish_announced = false;
+    }
+
+    /// Share of the current phase that has elapsed, from 0.0 to 1.0.
+    fn progress_ratio(&self) -> f64 {
+        if self.phase_seconds == 0 {
+            return 1.0;
+        }
+        (self.elapsed_seconds() as f64 / self.phase_seconds as f64).min(1.0)
+    }
+
+    /// Seconds of the current phase that have already counted down.
+    fn elapsed_seconds(&self) -> u32 {
+        self.phase_seconds.saturating_sub(self.remaining_seconds)
+    }
+
+    fn reset_to_interruption(&mut self) {
+        self.phase = TimerPhase::Interruption;
+        self.start_countdown(Self::interruption_duration());
         self.is_paused = false;
         self.last_update = None;
     }
 
+    /// One-line summary like "🍅 24:13", or "⏸ 24:13" while paused.
+    fn status_line(&self) -> String {
+        let icon = if self.is_paused { "⏸" } else { self.emoji() };
+        format!("{} {}", icon, self.format_time())
+    }
+
+    /// Machine-readable snapshot printed by `pomo status --json`.
+    fn status_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "phase": self.phase,
+            "remaining_seconds": self.remaining_seconds,
+            "is_paused": self.is_paused,
+            "formatted": self.format_time(),
+        })
+    }
+
     fn format_time(&self) -> String {
         let minutes = self.remaining_seconds / 60;
         let seconds = self.remaining_seconds % 60;
         format!("{:02}:{:02}", minutes, seconds)
     }
 
+    fn title(&self, format: TitleFormat) -> String {
+        match format {
+            TitleFormat::Exact => format!("{} {} - pomo", self.emoji(), self.format_time()),
+            TitleFormat::Rounded => format!("({}) pomo", self.remaining_seconds.div_ceil(60)),
+        }
+    }
+
     fn emoji(&self) -> &'static str {
         match self.phase {
             TimerPhase::Work => "🍅",
             TimerPhase::Break => "🌴",
+            TimerPhase::LongBreak => "☕",
+            TimerPhase::Interruption => "⚡",
         }
     }
 
     fn update(&mut self) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        self.update_at(unix_now());
+    }
 
+    fn update_at(&mut self, now: u64) {
         if let Some(last_update) = self.last_update {
             if !self.is_paused {
-                let elapsed = now - last_update;
+                // A clock that jumped backwards counts as no time passing
+                let elapsed = now.saturating_sub(last_update);
                 if elapsed > 0 {
                     if self.remaining_seconds > elapsed as u32 {
                         self.remaining_seconds -= elapsed as u32;
@@ -110,234 +737,2737 @@ impl TimerState {
 
     fn toggle_pause(&mut self) {
         self.is_paused = !self.is_paused;
+        // Resuming starts counting from the next update, so a baseline left
+        // over from before the pause (or a reload) never eats into the time
+        if !self.is_paused {
+            self.last_update = None;
+        }
     }
-}
-
-fn get_config_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-    PathBuf::from(home).join(".pomo.json")
-}
 
-fn load_state() -> TimerState {
-    let config_path = get_config_path();
-    if let Ok(contents) = fs::read_to_string(&config_path) {
-        if let Ok(mut state) = serde_json::from_str::<TimerState>(&contents) {
-            state.update();
-            return state;
+    /// Resumes the timer if it's paused, returning whether it was.
+    fn resume(&mut self) -> bool {
+        let was_paused = self.is_paused;
+        if was_paused {
+            self.toggle_pause();
         }
+        was_paused
     }
-    TimerState::default()
-}
-
-fn save_state(state: &TimerState) -> Result<()> {
-    let config_path = get_config_path();
-    let contents = serde_json::to_string_pretty(state)?;
-    fs::write(&config_path, contents)?;
-    Ok(())
-}
 
-fn show_notification(message: &str) {
-    let _ = Notification::new()
-        .summary("Pomodoro Timer")
-        .body(message)
-        .show();
-}
+    /// Marks the state as owned by a running timer until `clean_exit` is set
+    /// again on quit. Returns whether the previous run ended without a clean
+    /// exit, pausing the timer in that case if asked to.
+    fn begin_run(&mut self, pause_if_crashed: bool) -> bool {
+        let crashed = !self.clean_exit;
+        if crashed && pause_if_crashed {
+            self.is_paused = true;
+        }
+        self.clean_exit = false;
+        crashed
+    }
 
-async fn run_timer() -> Result<()> {
-    let mut state = load_state();
-    let mut last_save = Instant::now();
-    let save_interval = Duration::from_secs(5);
+    /// Jumps to wherever a shared schedule anchored at `anchor` is at `now`.
+    fn sync_to(&mut self, anchor: u64, now: u64) {
+        let break_seconds = self
+            .break_range
+            .map_or(Self::break_duration(), |range| range.min * 60);
+        let (phase, remaining) = synced_position(anchor, now, self.work_seconds, break_seconds);
+        self.phase = phase;
+        self.phase_seconds = match phase {
+            TimerPhase::Work => self.work_seconds,
+            _ => break_seconds,
+        };
+        if phase == TimerPhase::Break {
+            self.picked_break_seconds = Some(break_seconds);
+        }
+        self.remaining_seconds = remaining;
+        self.finish_announced = false;
+        self.last_update = Some(now);
+    }
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
+    /// Whether `--commit` currently forbids adjusting or skipping the phase.
+    fn is_committed(&self, commit: bool) -> bool {
+        commit && self.phase == TimerPhase::Work
+    }
 
-    loop {
-        // Clear screen and move cursor to top
-        execute!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    fn daily_limit_reached(&self, day: i64, limit: u32) -> bool {
+        self.completed_day == Some(day) && self.completed_today >= limit
+    }
 
-        // Update state
-        state.update();
+    /// Holds a work phase from starting while today's hard session limit is
+    /// reached. Returns whether the timer is being held.
+    fn hold_for_daily_limit(&mut self, day: i64, limit: Option<u32>) -> bool {
+        let held = self.phase == TimerPhase::Work
+            && limit.is_some_and(|limit| self.daily_limit_reached(day, limit));
+        if held {
+            // Don't let the wait count against the session once it can start
+            self.last_update = None;
+        }
+        held
+    }
 
-        // Display timer
-        println!("{} {}", state.format_time(), state.emoji());
-        println!();
-        if state.is_paused {
-            println!("PAUSED - Press 'r' to resume, 'q' to quit");
-        } else {
-            println!("Press 'p' to pause, 'q' to quit");
+    /// Marks a distraction during the current work session.
+    fn mark_interruption(&mut self) {
+        if self.phase == TimerPhase::Work {
+            self.interruptions += 1;
         }
+    }
 
-        stdout.flush()?;
+    /// Tags the current work session, or clears the tag if it already has it.
+    fn toggle_tag(&mut self, tag: SessionTag) {
+        if self.phase == TimerPhase::Work {
+            self.tag = if self.tag == Some(tag) {
+                None
+            } else {
+                Some(tag)
+            };
+        }
+    }
 
-        // Check if timer finished
-        if state.is_finished() {
-            match state.phase {
-                TimerPhase::Work => {
-                    show_notification("Work session completed! Time for a break.");
-                    state.reset_to_break();
-                }
-                TimerPhase::Break => {
-                    show_notification("Break time over! Ready for work?");
-                    state.reset_to_work();
-                }
+    /// Counts a completed work session on the given local day, keeping the
+    /// per-day tally and the run of consecutive days with a session.
+    fn record_completed_work_on(&mut self, day: i64) {
+        match self.completed_day {
+            Some(last) if last == day => {}
+            Some(last) if last == day - 1 => {
+                self.streak_days += 1;
+                self.completed_today = 0;
+            }
+            _ => {
+                self.streak_days = 1;
+                self.completed_today = 0;
             }
         }
+        self.completed_day = Some(day);
+        self.completed_today += 1;
+        self.completed_work_sessions += 1;
+    }
 
-        // Save state periodically
-        if last_save.elapsed() >= save_interval {
-            save_state(&state)?;
-            last_save = Instant::now();
+    /// Adds or removes time from the live countdown. Adding stops at
+    /// `MAX_ADJUSTED_SECONDS` and removing stops at 00:00.
+    fn adjust_minutes(&mut self, minutes: i32) {
+        let delta = minutes.unsigned_abs().saturating_mul(60);
+        let before = self.remaining_seconds;
+        if minutes >= 0 {
+            let cap = MAX_ADJUSTED_SECONDS.max(self.remaining_seconds);
+            self.remaining_seconds = self.remaining_seconds.saturating_add(delta).min(cap);
+        } else {
+            self.remaining_seconds = self.remaining_seconds.saturating_sub(delta);
         }
+        // The phase grows or shrinks by the same amount
+        self.phase_seconds = self
+            .phase_seconds
+            .saturating_add(self.remaining_seconds)
+            .saturating_sub(before);
+    }
 
-        // Check for input (non-blocking)
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('p') if !state.is_paused => {
-                        state.toggle_pause();
-                    }
-                    KeyCode::Char('r') if state.is_paused => {
-                        state.toggle_pause();
-                    }
-                    KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
-                        break;
-                    }
-                    _ => {}
-                }
-            }
-        }
+    fn is_break(&self) -> bool {
+        matches!(self.phase, TimerPhase::Break | TimerPhase::LongBreak)
+    }
 
-        // Wait a bit before next update
-        time::sleep(Duration::from_millis(100)).await;
+    /// Saves what's left of the current break for later, up to the cap.
+    fn bank_break(&mut self) {
+        if self.is_break() {
+            self.banked_break_seconds = self
+                .banked_break_seconds
+                .saturating_add(self.remaining_seconds)
+                .min(MAX_BANKED_BREAK_SECONDS);
+        }
     }
 
-    disable_raw_mode()?;
-    save_state(&state)?;
-    Ok(())
+    /// Adds all banked time to the current break. Returns false outside a
+    /// break or with nothing banked.
+    fn cash_in_break(&mut self) -> bool {
+        if !self.is_break() || self.banked_break_seconds == 0 {
+            return false;
+        }
+        let banked = std::mem::take(&mut self.banked_break_seconds);
+        self.remaining_seconds = self.remaining_seconds.saturating_add(banked);
+        self.phase_seconds = self.phase_seconds.saturating_add(banked);
+        true
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let _cli = Cli::parse();
+/// Coalesces rapid +/- presses so key-repeat can't run away with the timer.
+struct AdjustmentBurst {
+    max_minutes: i32,
+    net_minutes: i32,
+    last_press: Option<Instant>,
+}
 
-    // Set up Ctrl+C handler
-    let original_hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |panic_info| {
-        let _ = disable_raw_mode();
-        original_hook(panic_info);
-    }));
+impl AdjustmentBurst {
+    fn new(max_minutes: u32) -> Self {
+        Self {
+            max_minutes: max_minutes.min(i32::MAX as u32) as i32,
+            net_minutes: 0,
+            last_press: None,
+        }
+    }
 
-    if let Err(e) = run_timer().await {
-        disable_raw_mode()?;
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    fn is_active(&self, now: Instant) -> bool {
+        self.last_press
+            .is_some_and(|last| now.duration_since(last) < ADJUST_BURST_WINDOW)
     }
 
-    Ok(())
-}
+    /// When the indicator of the current burst disappears.
+    fn expires_at(&self, now: Instant) -> Option<Instant> {
+        self.last_press
+            .map(|last| last + ADJUST_BURST_WINDOW)
+            .filter(|&expiry| expiry > now && self.net_minutes != 0)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Registers a press of `delta` minutes and returns how many minutes
+    /// should actually be applied once the burst cap is taken into account.
+    fn press(&mut self, delta: i32, now: Instant) -> i32 {
+        if !self.is_active(now) {
+            self.net_minutes = 0;
+        }
+        self.last_press = Some(now);
 
-    #[test]
-    fn test_timer_state_default() {
-        let state = TimerState::default();
-        assert_eq!(state.phase, TimerPhase::Work);
-        assert_eq!(state.remaining_seconds, 25 * 60);
-        assert!(!state.is_paused);
-        assert!(state.last_update.is_none());
+        let next = (self.net_minutes + delta).clamp(-self.max_minutes, self.max_minutes);
+        let applied = next - self.net_minutes;
+        self.net_minutes = next;
+        applied
     }
 
-    #[test]
-    fn test_timer_phase_durations() {
-        assert_eq!(TimerState::work_duration(), 25 * 60);
-        assert_eq!(TimerState::break_duration(), 5 * 60);
+    /// Transient indicator like "+2m" showing the net adjustment of the current burst.
+    fn indicator(&self, now: Instant) -> Option<String> {
+        if self.is_active(now) && self.net_minutes != 0 {
+            Some(format!("{:+}m", self.net_minutes))
+        } else {
+            None
+        }
     }
+}
 
-    #[test]
-    fn test_timer_state_format_time() {
-        let mut state = TimerState::default();
+const POINTS_PER_SESSION: u32 = 10;
+const FULL_CYCLE_BONUS: u32 = 20;
+const MAX_STREAK_BONUS: u32 = 10;
+const XP_PER_LEVEL: u32 = 100;
 
-        // Test 25:00
-        state.remaining_seconds = 25 * 60;
+/// Points for completing a pomodoro, given the current streak of consecutive
+/// days and whether it completes a cycle, so a long break comes next.
+fn compute_points(streak: u32, completes_cycle: bool) -> u32 {
+    let mut points = POINTS_PER_SESSION + streak.saturating_sub(1).min(MAX_STREAK_BONUS);
+    if completes_cycle {
+        points += FULL_CYCLE_BONUS;
+    }
+    points
+}
+
+/// Level and XP bar like "Lv 2 [██████░░░░] 60/100 XP".
+fn format_xp(xp: u32) -> String {
+    const WIDTH: u32 = 10;
+    let level = xp / XP_PER_LEVEL + 1;
+    let progress = xp % XP_PER_LEVEL;
+    let filled = (progress * WIDTH / XP_PER_LEVEL) as usize;
+    format!(
+        "Lv {} [{}{}] {}/{} XP",
+        level,
+        "█".repeat(filled),
+        "░".repeat(WIDTH as usize - filled),
+        progress,
+        XP_PER_LEVEL
+    )
+}
+
+/// Widest the progress bar grows on large terminals, in cells.
+const MAX_PROGRESS_WIDTH: usize = 40;
+
+/// Slack for float error, so a ratio like 0.29 reads as 29% rather than 28%.
+const RATIO_EPSILON: f64 = 1e-9;
+
+/// Rounds `ratio` down to a whole number of `step_percent` steps, so the
+/// bar and percentage only change once per step.
+fn quantize_ratio(ratio: f64, step_percent: Option<u32>) -> f64 {
+    match step_percent {
+        Some(step) => {
+            let percent = (ratio * 100.0 + RATIO_EPSILON) as u32;
+            f64::from(percent - percent % step) / 100.0
+        }
+        None => ratio,
+    }
+}
+
+/// Progress bar like "[██████░░░░] 60%" that fits within `columns`.
+fn format_progress_bar(ratio: f64, columns: u16) -> String {
+    // Room for the brackets and " 100%"
+    let width = usize::from(columns)
+        .saturating_sub(7)
+        .min(MAX_PROGRESS_WIDTH);
+    let filled = ((ratio * width as f64 + RATIO_EPSILON) as usize).min(width);
+    format!(
+        "[{}{}] {}%",
+        "█".repeat(filled),
+        "░".repeat(width - filled),
+        (ratio * 100.0 + RATIO_EPSILON) as u32
+    )
+}
+
+/// How long a finished work session waits for a click on the notification's
+/// buttons before moving on by itself.
+const ACTION_WAIT: Duration = Duration::from_secs(5);
+
+/// A second 'q' within this window confirms quitting under `--double-quit`.
+const DOUBLE_QUIT_WINDOW: Duration = Duration::from_millis(1500);
+
+/// Tracks 'q' presses so an accidental single press doesn't quit.
+#[derive(Default)]
+struct QuitConfirm {
+    last_press: Option<Instant>,
+}
+
+impl QuitConfirm {
+    fn is_pending(&self, now: Instant) -> bool {
+        self.last_press
+            .is_some_and(|last| now.duration_since(last) < DOUBLE_QUIT_WINDOW)
+    }
+
+    /// When the "Press q again" prompt disappears.
+    fn expires_at(&self, now: Instant) -> Option<Instant> {
+        self.last_press
+            .map(|last| last + DOUBLE_QUIT_WINDOW)
+            .filter(|&expiry| expiry > now)
+    }
+
+    /// Registers a 'q' press and returns whether it completes a double press.
+    fn press(&mut self, now: Instant) -> bool {
+        if self.is_pending(now) {
+            return true;
+        }
+        self.last_press = Some(now);
+        false
+    }
+}
+
+/// Transient state shown after a phase finishes, so the transition is
+/// visible before the next phase starts counting down. Under `--manual` it
+/// lasts until Space is pressed.
+struct PhaseDwell {
+    finished: TimerPhase,
+    until: Option<Instant>,
+}
+
+impl PhaseDwell {
+    fn new(finished: TimerPhase, now: Instant, duration: Duration) -> Self {
+        Self {
+            finished,
+            until: Some(now + duration),
+        }
+    }
+
+    /// A dwell that waits for the user to start the next phase.
+    fn manual(finished: TimerPhase) -> Self {
+        Self {
+            finished,
+            until: None,
+        }
+    }
+
+    fn is_over(&self, now: Instant) -> bool {
+        self.until.is_some_and(|until| now >= until)
+    }
+
+    fn message(&self) -> &'static str {
+        match (self.finished, self.until) {
+            (TimerPhase::Work, Some(_)) => "Work complete! Break starting…",
+            (TimerPhase::Break, Some(_)) => "Break over! Work starting…",
+            (TimerPhase::LongBreak, Some(_)) => "Long break over! Work starting…",
+            (TimerPhase::Interruption, Some(_)) => "Interruption over! Resuming your session…",
+            (TimerPhase::Work, None) => {
+                "Work complete — press space to start your break, 'q' to quit"
+            }
+            (TimerPhase::Break | TimerPhase::LongBreak, None) => {
+                "Break over — press space to start working, 'q' to quit"
+            }
+            (TimerPhase::Interruption, None) => {
+                "Interruption over — press space to resume your session, 'q' to quit"
+            }
+        }
+    }
+}
+
+/// Focus label being typed in after pressing 't', edited inside the
+/// raw-mode loop since there's no line discipline to lean on.
+struct LabelEdit {
+    text: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum LabelEditStep {
+    Editing,
+    Save(Option<String>),
+    Cancel,
+}
+
+impl LabelEdit {
+    fn new(current: Option<&str>) -> Self {
+        Self {
+            text: current.unwrap_or_default().to_string(),
+        }
+    }
+
+    fn key(&mut self, code: KeyCode) -> LabelEditStep {
+        match code {
+            KeyCode::Enter => {
+                let label = self.text.trim();
+                LabelEditStep::Save((!label.is_empty()).then(|| label.to_string()))
+            }
+            KeyCode::Esc => LabelEditStep::Cancel,
+            KeyCode::Backspace => {
+                self.text.pop();
+                LabelEditStep::Editing
+            }
+            KeyCode::Char(c) => {
+                self.text.push(c);
+                LabelEditStep::Editing
+            }
+            _ => LabelEditStep::Editing,
+        }
+    }
+
+    fn prompt(&self) -> String {
+        format!(
+            "Task: {}_  (Enter to save, empty to clear, Esc to cancel)",
+            self.text
+        )
+    }
+}
+
+/// What the space bar does at a given point in the loop.
+#[derive(Debug, PartialEq)]
+enum SpaceAction {
+    StartNextPhase,
+    TogglePause,
+}
+
+/// Space starts the next phase while a finished phase is waiting to move on,
+/// and toggles pause at any other time.
+fn space_action(dwell: Option<&PhaseDwell>) -> SpaceAction {
+    match dwell {
+        Some(_) => SpaceAction::StartNextPhase,
+        None => SpaceAction::TogglePause,
+    }
+}
+
+/// How far past each whole second the loop wakes, so the new second has
+/// always begun by the time the timer is updated.
+const TICK_SLACK: Duration = Duration::from_millis(5);
+
+/// Time until just after the next whole Unix second, when the countdown
+/// next changes.
+fn until_next_second(now: SystemTime) -> Duration {
+    let into_second = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(Duration::ZERO, |since| {
+            Duration::from_nanos(u64::from(since.subsec_nanos()))
+        });
+    Duration::from_secs(1) - into_second + TICK_SLACK
+}
+
+/// How long the main loop sleeps with no key, message or dwell due. At the
+/// default refresh of a second that's until the countdown next changes;
+/// shorter refreshes wake in between, and longer ones skip whole seconds,
+/// though never past the end of a running phase (`remaining_seconds`).
+fn idle_wait(
+    until_next_second: Duration,
+    refresh: Duration,
+    remaining_seconds: Option<u32>,
+) -> Duration {
+    if refresh < Duration::from_secs(1) {
+        return until_next_second.min(refresh);
+    }
+    let seconds = refresh
+        .as_secs()
+        .min(remaining_seconds.map_or(u64::MAX, u64::from))
+        .max(1);
+    until_next_second + Duration::from_secs(seconds - 1)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Day index (days since the Unix epoch) of a timestamp shifted by a UTC offset.
+fn day_index(epoch_secs: u64, utc_offset_secs: i64) -> i64 {
+    (epoch_secs as i64 + utc_offset_secs).div_euclid(24 * 60 * 60)
+}
+
+/// Day index in the local timezone, so day boundaries fall at local midnight.
+fn local_day(epoch_secs: u64) -> i64 {
+    day_index(epoch_secs, local_utc_offset(epoch_secs))
+}
+
+#[cfg(unix)]
+fn local_utc_offset(epoch_secs: u64) -> i64 {
+    let time = epoch_secs as libc::time_t;
+    // SAFETY: `tm` is plain old data and `localtime_r` only writes into it.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(not(unix))]
+fn local_utc_offset(_epoch_secs: u64) -> i64 {
+    0
+}
+
+fn get_config_path() -> PathBuf {
+    resolve_config_path(dirs::home_dir(), dirs::config_dir())
+}
+
+/// Default state file given the user's home and config directories. A
+/// legacy `~/.pomo.json` from older versions keeps being used so existing
+/// state and history stay where they are.
+fn resolve_config_path(home: Option<PathBuf>, config_dir: Option<PathBuf>) -> PathBuf {
+    if let Some(legacy) = home.map(|home| home.join(".pomo.json")) {
+        if legacy.exists() {
+            return legacy;
+        }
+    }
+    match config_dir {
+        Some(dir) => dir.join("pomo").join("state.json"),
+        // Without any notion of a home directory there's nowhere better
+        None => std::env::temp_dir().join(".pomo.json"),
+    }
+}
+
+/// A file kept next to the state file, named after it: `state.json` gets
+/// `state-stash.json` for the suffix "-stash.json".
+fn sibling_path(state_path: &Path, suffix: &str) -> PathBuf {
+    let stem = state_path.file_stem().unwrap_or_default().to_string_lossy();
+    state_path.with_file_name(format!("{}{}", stem, suffix))
+}
+
+fn load_state(path: &Path) -> TimerState {
+    if let Ok(contents) = fs::read_to_string(path) {
+        if let Ok(state) = serde_json::from_str::<TimerState>(&contents) {
+            return state;
+        }
+    }
+    TimerState::default()
+}
+
+fn save_state(path: &Path, state: &TimerState) -> Result<()> {
+    let contents = serde_json::to_string_pretty(state)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+fn get_stash_path(state_path: &Path) -> PathBuf {
+    sibling_path(state_path, "-stash.json")
+}
+
+/// Sets the current session aside so an interruption timer can run.
+fn stash_state(path: &Path, state: &TimerState) -> Result<()> {
+    let contents = serde_json::to_string_pretty(state)?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Takes the stashed session back, removing the stash file. Time spent while
+/// stashed is not counted against the restored session.
+fn take_stash(path: &Path) -> Option<TimerState> {
+    let contents = fs::read_to_string(path).ok()?;
+    let _ = fs::remove_file(path);
+    let mut state = serde_json::from_str::<TimerState>(&contents).ok()?;
+    state.last_update = None;
+    Some(state)
+}
+
+/// Ends an interruption by restoring the stashed session, or starting a
+/// fresh work session if the stash has gone missing. Only the session itself
+/// comes back: settings and the label changed meanwhile are kept.
+fn restore_from_stash(state: &mut TimerState, path: &Path) {
+    let Some(stashed) = take_stash(path) else {
+        state.reset_to_work();
+        return;
+    };
+    state.phase = stashed.phase;
+    state.remaining_seconds = stashed.remaining_seconds;
+    state.phase_seconds = stashed.phase_seconds;
+    state.is_paused = stashed.is_paused;
+    state.last_update = stashed.last_update;
+    state.finish_announced = stashed.finish_announced;
+    state.interruptions = stashed.interruptions;
+    state.tag = stashed.tag;
+    state.schedule = stashed.schedule;
+}
+
+/// Runs external commands; abstracted so tests can stub out invocations.
+trait CommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<()>;
+}
+
+struct SystemCommandRunner;
+
+impl CommandRunner for SystemCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<()> {
+        let status = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "{} exited with {}",
+                program, status
+            )))
+        }
+    }
+}
+
+/// Screen-lock commands to try, in order, until one succeeds.
+#[cfg(target_os = "macos")]
+const LOCK_COMMANDS: &[(&str, &[&str])] = &[("pmset", &["displaysleepnow"])];
+
+#[cfg(windows)]
+const LOCK_COMMANDS: &[(&str, &[&str])] = &[("rundll32.exe", &["user32.dll,LockWorkStation"])];
+
+#[cfg(not(any(target_os = "macos", windows)))]
+const LOCK_COMMANDS: &[(&str, &[&str])] = &[
+    ("loginctl", &["lock-session"]),
+    ("xdg-screensaver", &["lock"]),
+    ("gnome-screensaver-command", &["--lock"]),
+    ("xset", &["s", "activate"]),
+];
+
+/// Locks the screen with the first lock command that works. Failure is not
+/// fatal; returns whether the screen was locked.
+fn lock_screen(runner: &dyn CommandRunner) -> bool {
+    LOCK_COMMANDS
+        .iter()
+        .any(|(program, args)| runner.run(program, args).is_ok())
+}
+
+/// Shell that runs `--on-*` hook commands.
+#[cfg(windows)]
+const HOOK_SHELL: (&str, &str) = ("cmd", "/C");
+
+#[cfg(not(windows))]
+const HOOK_SHELL: (&str, &str) = ("sh", "-c");
+
+/// Starts a hook command with the phase name in `POMO_PHASE` (and as `$1`
+/// under sh).
+fn spawn_hook(command: &str, phase: TimerPhase) -> io::Result<Child> {
+    let (shell, flag) = HOOK_SHELL;
+    let mut hook = Command::new(shell);
+    hook.arg(flag).arg(command);
+    #[cfg(not(windows))]
+    hook.arg("pomo").arg(phase.name());
+    hook.env("POMO_PHASE", phase.name())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+/// Runs the hook for the phase just entered in the background. A hook that
+/// fails is reported but never stops the timer.
+fn run_phase_hook(cli: &Cli, phase: TimerPhase) {
+    let Some(command) = cli.hook_for(phase) else {
+        return;
+    };
+    match spawn_hook(command, phase) {
+        Ok(mut child) => {
+            let command = command.to_string();
+            std::thread::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => {
+                    eprintln!("Error: hook `{}` exited with {}", command, status)
+                }
+                Err(e) => eprintln!("Error: hook `{}` failed: {}", command, e),
+                Ok(_) => {}
+            });
+        }
+        Err(e) => eprintln!("Error: failed to run hook `{}`: {}", command, e),
+    }
+}
+
+/// Notification channels enabled on the command line.
+fn build_notifiers(cli: &Cli) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    if !cli.no_notify {
+        notifiers.push(Box::new(DesktopNotifier {
+            timeout: cli.notify_timeout,
+        }));
+    }
+    if cli.bell {
+        notifiers.push(Box::new(BellNotifier));
+    }
+    notifiers
+}
+
+/// Records the phase that just reached 00:00 and announces it on every
+/// enabled notification channel. A finished work session is announced with
+/// buttons where the channel supports them; the returned receiver yields
+/// the one clicked.
+fn finish_phase(
+    state: &mut TimerState,
+    cli: &Cli,
+    notifiers: &[Box<dyn Notifier>],
+    history_path: &Path,
+) -> Option<mpsc::Receiver<NotificationAction>> {
+    if state.finish_announced {
+        return None;
+    }
+    state.finish_announced = true;
+    if state.phase == TimerPhase::Work {
+        record_work_session(state, cli, history_path);
+    }
+    let template = match state.phase {
+        TimerPhase::Work => cli.work_message.as_deref(),
+        TimerPhase::Break | TimerPhase::LongBreak => cli.break_message.as_deref(),
+        TimerPhase::Interruption => None,
+    };
+    let event = TransitionEvent {
+        finished: state.phase,
+        message: match template {
+            Some(template) => render_template(template, &notification_values(state)),
+            None => default_message(state.phase).to_string(),
+        },
+    };
+    let mut response = None;
+    for notifier in notifiers {
+        if state.phase == TimerPhase::Work {
+            response = response.or(notifier.notify_with_actions(&event));
+        } else {
+            notifier.notify(&event);
+        }
+    }
+    if cli.celebrate && !cli.quiet && state.phase == TimerPhase::Work && state.long_break_due() {
+        let celebration = TransitionEvent {
+            finished: state.phase,
+            message: set_summary(
+                state.completed_work_sessions,
+                set_focus_seconds(history_path, state.completed_work_sessions),
+            ),
+        };
+        for notifier in notifiers {
+            notifier.notify(&celebration);
+        }
+    }
+    response
+}
+
+/// Focus time of the last `sessions` logged work sessions, which make up
+/// the set just completed.
+fn set_focus_seconds(history_path: &Path, sessions: u32) -> u64 {
+    let records = history::load(history_path).unwrap_or_default();
+    records
+        .iter()
+        .rev()
+        .filter(|record| record.phase == TimerPhase::Work)
+        .take(sessions as usize)
+        .map(|record| u64::from(record.duration_seconds))
+        .sum()
+}
+
+/// Counts the work session `state` is in as completed, whether it ran out or
+/// was skipped, awards its XP under `--gamify` and appends it to the session
+/// history. A session skipped before any time was spent on it doesn't
+/// count. A failed write is reported but doesn't stop the timer.
+fn record_work_session(state: &mut TimerState, cli: &Cli, history_path: &Path) {
+    if state.phase != TimerPhase::Work || state.elapsed_seconds() == 0 {
+        return;
+    }
+    let now = unix_now();
+    state.record_completed_work_on(local_day(now));
+    if cli.gamify {
+        state.xp += compute_points(state.streak_days, state.long_break_due());
+    }
+    if let Err(e) = history::append(history_path, &SessionRecord::new(state, now)) {
+        eprintln!("Error: {:#}", e);
+    }
+}
+
+/// Values available to `--work-message`/`--break-message` placeholders.
+fn notification_values(state: &TimerState) -> [(&'static str, String); 3] {
+    let today_count = if state.completed_day == Some(local_day(unix_now())) {
+        state.completed_today
+    } else {
+        0
+    };
+    let next_phase = match state.phase {
+        TimerPhase::Work if state.long_break_due() => "long break",
+        TimerPhase::Work => "break",
+        TimerPhase::Break | TimerPhase::LongBreak => "work",
+        TimerPhase::Interruption => "session",
+    };
+    [
+        ("today_count", today_count.to_string()),
+        ("streak", state.streak_days.to_string()),
+        ("next_phase", next_phase.to_string()),
+    ]
+}
+
+/// Moves on from a finished phase to the one that follows it.
+fn start_next_phase(state: &mut TimerState, cli: &Cli, stash_path: &Path) {
+    match state.phase {
+        TimerPhase::Work => state.reset_to_next_break(),
+        TimerPhase::Break | TimerPhase::LongBreak => state.reset_to_work(),
+        TimerPhase::Interruption => restore_from_stash(state, stash_path),
+    }
+    // Re-align each phase so dwells and notifications never cause drift
+    if let Some(anchor) = cli.sync {
+        state.sync_to(anchor, unix_now());
+    }
+}
+
+/// Moves on from a finished or skipped phase, following the schedule when
+/// one is running, locks the screen for a break under `--lock-on-break` and
+/// runs the hook for the phase entered. Returns false once the schedule has
+/// no blocks left.
+fn advance(
+    state: &mut TimerState,
+    cli: &Cli,
+    stash_path: &Path,
+    schedule: Option<&Schedule>,
+) -> bool {
+    let from_work = state.phase == TimerPhase::Work;
+    // A break that ran out has nothing left to bank
+    if cli.bank_breaks {
+        state.bank_break();
+    }
+    let advanced = match schedule {
+        Some(schedule) if state.phase != TimerPhase::Interruption => schedule.advance(state),
+        _ => {
+            start_next_phase(state, cli, stash_path);
+            true
+        }
+    };
+    if advanced {
+        let on_break = matches!(state.phase, TimerPhase::Break | TimerPhase::LongBreak);
+        if cli.lock_on_break && from_work && on_break {
+            lock_screen(&SystemCommandRunner);
+        }
+        run_phase_hook(cli, state.phase);
+    }
+    advanced
+}
+
+/// Goes straight from a finished work session to the next one, leaving out
+/// the break (banking it under `--bank-breaks`), and runs the work hook.
+/// Returns false once the schedule has no blocks left.
+fn skip_break(state: &mut TimerState, cli: &Cli, schedule: Option<&Schedule>) -> bool {
+    let advanced = match schedule {
+        Some(schedule) => {
+            schedule.advance(state) && {
+                if cli.bank_breaks {
+                    state.bank_break();
+                }
+                state.phase == TimerPhase::Work || schedule.advance(state)
+            }
+        }
+        None => {
+            if cli.bank_breaks {
+                state.reset_to_next_break();
+                state.bank_break();
+            }
+            state.reset_to_work();
+            if let Some(anchor) = cli.sync {
+                state.sync_to(anchor, unix_now());
+            }
+            true
+        }
+    };
+    if advanced {
+        run_phase_hook(cli, state.phase);
+    }
+    advanced
+}
+
+async fn run_timer(cli: &Cli, schedule: Option<&Schedule>) -> Result<()> {
+    let state_path = cli.state_path();
+    let _lock = lock::InstanceLock::acquire(&lock::get_lock_path(&state_path))?;
+    let mut state = load_state(&state_path);
+    let mut crash_note = state.begin_run(cli.pause_after_crash) && cli.pause_after_crash;
+    save_state(&state_path, &state)?;
+    state.warmup_seconds = cli.warmup.map(|minutes| minutes * 60);
+    state.configure_durations(cli.work, cli.break_range);
+    state.configure_long_breaks(cli.long_break_every, cli.long_break);
+    if let Some(task) = &cli.task {
+        state.label = Some(task.clone());
+    }
+    match schedule {
+        Some(schedule) => schedule.resume(&mut state),
+        None => state.schedule = None,
+    }
+    if let Some(anchor) = cli.sync.filter(|_| schedule.is_none()) {
+        if state.fixed_break_seconds().is_none() {
+            anyhow::bail!("--sync needs a fixed --break length so everyone shares one schedule");
+        }
+        state.sync_to(anchor, unix_now());
+    }
+    // A headless run has no key to resume with, so a timer saved paused
+    // would otherwise sit there for good
+    if cli.quiet && state.resume() {
+        println!(
+            "{}",
+            output_line(
+                cli,
+                "Resuming the timer, which was saved paused",
+                unix_now()
+            )
+        );
+    }
+    let mut last_save = Instant::now();
+    let save_interval = Duration::from_secs(5);
+    let mut adjustment = AdjustmentBurst::new(cli.max_adjust);
+    let refresh = Duration::from_millis(cli.refresh);
+    let stash_path = get_stash_path(&state_path);
+    let history_path = get_history_path(&state_path);
+    let dwell_duration = Duration::from_secs(cli.dwell);
+    let mut dwell: Option<PhaseDwell> = None;
+    let mut action_response: Option<mpsc::Receiver<NotificationAction>> = None;
+    let mut quit_confirm = QuitConfirm::default();
+    let mut label_edit: Option<LabelEdit> = None;
+    // Stats shown over the timer after pressing 'S', until the next key
+    let mut stats_overlay: Option<Vec<String>> = None;
+    let notifiers = Arc::new(build_notifiers(cli));
+    // Probe the notification backend in the background so a slow or missing
+    // daemon never delays startup
+    let mut notify_check = (!cli.no_notify).then(|| {
+        let notifiers = Arc::clone(&notifiers);
+        tokio::task::spawn_blocking(move || availability_warning(&notifiers))
+    });
+    let mut notify_warning = None;
+    #[cfg(feature = "power")]
+    let mut power_monitor = power::PowerMonitor::default();
+    #[cfg(all(unix, feature = "rpc"))]
+    let rpc_server = rpc::RpcServer::bind(&rpc::get_socket_path(&state_path))?;
+
+    // Headless runs have no keys to read, so Ctrl+C arrives as a signal
+    let mut shutdown = std::pin::pin!(shutdown_signal());
+
+    if !cli.quiet {
+        enable_raw_mode()?;
+    }
+    let mut stdout = io::stdout();
+    let (mut columns, _) = terminal::size().unwrap_or((80, 24));
+    let mut redraw = true;
+    let mut last_frame: Option<Frame> = None;
+    let theme = Theme::from_env();
+    let mut last_title: Option<String> = None;
+    if cli.title().is_some() {
+        execute!(stdout, Print(SAVE_TITLE))?;
+    }
+
+    loop {
+        let mut frame = Frame::default();
+
+        // Update state, unless today's work sessions are used up
+        let held = state.hold_for_daily_limit(local_day(unix_now()), cli.daily_limit);
+        if !held {
+            state.update();
+        }
+
+        // Display timer
+        if let Some(format) = cli.title() {
+            let title = state.title(format);
+            if last_title.as_ref() != Some(&title) {
+                execute!(stdout, SetTitle(&title))?;
+                last_title = Some(title);
+            }
+        }
+        let tone = theme.phase(state.phase, state.is_paused);
+        let time = match adjustment.indicator(Instant::now()) {
+            Some(indicator) => format!("{} {} {}", state.format_time(), state.emoji(), indicator),
+            None => format!("{} {}", state.format_time(), state.emoji()),
+        };
+        frame.styled(time, tone);
+        let ratio = quantize_ratio(state.progress_ratio(), cli.progress_step);
+        let bar = format_progress_bar(ratio, columns);
+        if state.is_paused {
+            frame.styled(bar, theme.dimmed());
+        } else {
+            frame.line(bar);
+        }
+        if let Some(label) = &state.label {
+            frame.line(format!("Focus: {}", label));
+        }
+        if let (Some(schedule), Some(progress)) = (schedule, &state.schedule) {
+            frame.line(format!("Block {}/{}", progress.block + 1, schedule.len()));
+        }
+        if state.phase == TimerPhase::Work {
+            if let Some(tag) = state.tag {
+                frame.line(format!("Type: {}", tag.name()));
+            }
+            if state.interruptions > 0 {
+                frame.line(format!("Interruptions: {}", state.interruptions));
+            }
+        }
+        if cli.gamify {
+            frame.line(format_xp(state.xp));
+        }
+        if state.banked_break_seconds > 0 {
+            let banked = format!("Banked break: {} min", state.banked_break_seconds / 60);
+            if state.is_break() {
+                frame.line(format!("{} - press 'b' to add it", banked));
+            } else {
+                frame.line(banked);
+            }
+        }
+        frame.line("");
+        if notify_check
+            .as_ref()
+            .is_some_and(|check| check.is_finished())
+        {
+            if let Some(check) = notify_check.take() {
+                notify_warning = check.await.ok().flatten();
+                if let Some(warning) = notify_warning.filter(|_| cli.quiet) {
+                    eprintln!("{}", output_line(cli, warning, unix_now()));
+                }
+            }
+        }
+        if let Some(warning) = notify_warning {
+            frame.line(warning);
+        }
+        if crash_note && !state.is_paused {
+            crash_note = false;
+        }
+        if crash_note {
+            frame.line("Recovered after an unclean exit; downtime was not counted.");
+        }
+        if let Some(rows) = &stats_overlay {
+            for row in rows {
+                frame.line(row.as_str());
+            }
+            frame.line("");
+        }
+        let status = if stats_overlay.is_some() {
+            "Press any key to return to the timer".to_string()
+        } else if let Some(edit) = &label_edit {
+            edit.prompt()
+        } else if cli.double_quit && quit_confirm.is_pending(Instant::now()) {
+            "Press q again to quit.".to_string()
+        } else if let Some(limit) = cli.daily_limit.filter(|_| held) {
+            format!(
+                "Daily limit of {} sessions reached - see you tomorrow! Press 'q' to quit",
+                limit
+            )
+        } else if let Some(dwell) = &dwell {
+            dwell.message().to_string()
+        } else if state.is_paused {
+            "PAUSED - Press 'r' to resume, 's' to skip, '+'/'-' to adjust, 'q' to quit".to_string()
+        } else if state.is_committed(cli.commit) {
+            "COMMITTED - Stay with it until the break! 'p' to pause, 'q' to quit".to_string()
+        } else if state.phase == TimerPhase::Interruption {
+            "INTERRUPTION - Press 'x' to return to your session, 'q' to quit".to_string()
+        } else {
+            "Press 'p' to pause, 's' to skip, 'R' to restart, '+'/'-' to adjust, 't' to set the task, 'i' to mark a distraction, 'x' for an interruption, 'q' to quit".to_string()
+        };
+        frame.styled(status, tone);
+
+        // Only redraw when something on screen actually changed
+        if !cli.quiet && (redraw || last_frame.as_ref() != Some(&frame)) {
+            frame.draw(&mut stdout, columns, redraw)?;
+            redraw = false;
+            last_frame = Some(frame);
+        }
+
+        // Check if timer finished, then hold the finished state briefly
+        // before moving on. Adding time during the dwell resumes the phase.
+        if !state.is_finished() {
+            dwell = None;
+        } else if dwell.is_none() {
+            action_response = finish_phase(&mut state, cli, &notifiers, &history_path);
+            let finished = if cli.manual {
+                PhaseDwell::manual(state.phase)
+            } else if action_response.is_some() {
+                // Give the notification's buttons a chance before moving on
+                PhaseDwell::new(state.phase, Instant::now(), dwell_duration.max(ACTION_WAIT))
+            } else {
+                PhaseDwell::new(state.phase, Instant::now(), dwell_duration)
+            };
+            if cli.quiet {
+                println!("{}", output_line(cli, finished.message(), unix_now()));
+            }
+            dwell = Some(finished);
+        }
+        let clicked = action_response
+            .as_ref()
+            .and_then(|response| response.try_recv().ok());
+        if let Some(action) = clicked.filter(|_| dwell.is_some()) {
+            dwell = None;
+            let advanced = match action {
+                NotificationAction::StartBreak => advance(&mut state, cli, &stash_path, schedule),
+                NotificationAction::SkipBreak => skip_break(&mut state, cli, schedule),
+            };
+            if !advanced {
+                break;
+            }
+        }
+        if dwell.as_ref().is_some_and(|d| d.is_over(Instant::now())) {
+            dwell = None;
+            if !advance(&mut state, cli, &stash_path, schedule) {
+                break;
+            }
+        }
+        // Clicks only count while the finished session is still on screen
+        if dwell.is_none() {
+            action_response = None;
+        }
+
+        #[cfg(feature = "power")]
+        if cli.auto_pause_power {
+            power_monitor.poll(&mut state, Instant::now());
+        }
+
+        // Service control requests between frames
+        #[cfg(all(unix, feature = "rpc"))]
+        {
+            let context = rpc::RequestContext {
+                cli,
+                schedule,
+                stash_path: &stash_path,
+                history_path: &history_path,
+            };
+            if !rpc_server.service(&mut state, &context) {
+                break;
+            }
+        }
+
+        // Save state periodically
+        if last_save.elapsed() >= save_interval {
+            save_state(&state_path, &state)?;
+            last_save = Instant::now();
+        }
+
+        // Sleep until the countdown next changes, a transient message needs
+        // clearing or a key arrives, whichever comes first
+        let now = Instant::now();
+        let wait = [
+            adjustment.expires_at(now),
+            quit_confirm.expires_at(now),
+            dwell.as_ref().and_then(|dwell| dwell.until),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|deadline| deadline.saturating_duration_since(now))
+        .fold(
+            idle_wait(
+                until_next_second(SystemTime::now()),
+                refresh,
+                (!state.is_paused).then_some(state.remaining_seconds),
+            ),
+            Duration::min,
+        );
+
+        if cli.quiet {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = time::sleep(wait) => {}
+            }
+            continue;
+        }
+
+        if event::poll(wait)? {
+            match event::read()? {
+                Event::Resize(width, _) => {
+                    columns = width;
+                    redraw = true;
+                }
+                Event::Key(_) if stats_overlay.is_some() => stats_overlay = None,
+                Event::Key(key) if label_edit.is_some() => {
+                    if key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(event::KeyModifiers::CONTROL)
+                    {
+                        break;
+                    }
+                    if let Some(edit) = label_edit.as_mut() {
+                        match edit.key(key.code) {
+                            LabelEditStep::Editing => {}
+                            LabelEditStep::Save(label) => {
+                                state.label = label;
+                                label_edit = None;
+                            }
+                            LabelEditStep::Cancel => label_edit = None,
+                        }
+                    }
+                }
+                Event::Key(key) => match key.code {
+                    KeyCode::Char('t') => {
+                        label_edit = Some(LabelEdit::new(state.label.as_deref()));
+                    }
+                    KeyCode::Char('q')
+                        if !cli.double_quit || quit_confirm.press(Instant::now()) =>
+                    {
+                        break
+                    }
+                    KeyCode::Char('p') if !state.is_paused => {
+                        state.toggle_pause();
+                    }
+                    KeyCode::Char('r') if state.is_paused => {
+                        state.toggle_pause();
+                    }
+                    KeyCode::Char(' ') => match space_action(dwell.as_ref()) {
+                        SpaceAction::StartNextPhase => {
+                            dwell = None;
+                            if !advance(&mut state, cli, &stash_path, schedule) {
+                                break;
+                            }
+                        }
+                        SpaceAction::TogglePause => state.toggle_pause(),
+                    },
+                    // Under --sync the shared schedule would put the timer
+                    // straight back, so there's nothing to skip to
+                    KeyCode::Char('s')
+                        if dwell.is_none()
+                            && !held
+                            && cli.sync.is_none()
+                            && !state.is_committed(cli.commit) =>
+                    {
+                        record_work_session(&mut state, cli, &history_path);
+                        if !advance(&mut state, cli, &stash_path, schedule) {
+                            break;
+                        }
+                    }
+                    KeyCode::Char('R')
+                        if dwell.is_none() && !held && !state.is_committed(cli.commit) =>
+                    {
+                        match schedule {
+                            Some(schedule) if state.phase != TimerPhase::Interruption => {
+                                schedule.restart_block(&mut state)
+                            }
+                            _ => state.restart_phase(),
+                        }
+                    }
+                    KeyCode::Char('+') | KeyCode::Char('-') | KeyCode::Up | KeyCode::Down
+                        if !held && !state.is_committed(cli.commit) =>
+                    {
+                        let delta = match key.code {
+                            KeyCode::Char('+') | KeyCode::Up => 1,
+                            _ => -1,
+                        };
+                        let applied = adjustment.press(delta, Instant::now());
+                        state.adjust_minutes(applied);
+                    }
+                    KeyCode::Char('S') => {
+                        stats_overlay = Some(match history::load(&history_path) {
+                            Ok(records) if records.is_empty() => {
+                                vec!["No sessions yet - finish a pomodoro and check back!".into()]
+                            }
+                            Ok(records) => history::summary_table(&records, unix_now()),
+                            Err(e) => vec![format!("Error: {:#}", e)],
+                        });
+                    }
+                    KeyCode::Char('b') => {
+                        state.cash_in_break();
+                    }
+                    KeyCode::Char('i') => {
+                        state.mark_interruption();
+                    }
+                    KeyCode::Char('d') => {
+                        state.toggle_tag(SessionTag::Deep);
+                    }
+                    KeyCode::Char('l') => {
+                        state.toggle_tag(SessionTag::Light);
+                    }
+                    KeyCode::Char('x') if state.phase == TimerPhase::Interruption => {
+                        restore_from_stash(&mut state, &stash_path);
+                    }
+                    KeyCode::Char('x') => {
+                        stash_state(&stash_path, &state)?;
+                        state.reset_to_interruption();
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        break;
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    if cli.title().is_some() {
+        // Clear the title, then restore the one saved at startup on
+        // terminals that support the title stack
+        execute!(stdout, SetTitle(""), Print(RESTORE_TITLE))?;
+    }
+    if !cli.quiet {
+        disable_raw_mode()?;
+    }
+    state.clean_exit = true;
+    save_state(&state_path, &state)?;
+    if schedule.is_some() && state.schedule.is_none() {
+        println!("{}", output_line(cli, "Schedule complete!", unix_now()));
+    }
+    Ok(())
+}
+
+/// Prints the live remaining time without touching the state file, so
+/// status bars can poll it while the timer runs.
+fn print_status(state_path: &Path, json: bool) {
+    let mut state = load_state(state_path);
+    state.update();
+    if json {
+        println!("{}", state.status_json());
+    } else {
+        println!("{}", state.status_line());
+    }
+}
+
+/// A line printed by a headless run, after the local time at `now` under
+/// `--timestamps` so it can be matched up with other logs.
+fn output_line(cli: &Cli, text: &str, now: u64) -> String {
+    if cli.timestamps {
+        format!(
+            "{} {}",
+            history::format_iso8601(now, local_utc_offset(now)),
+            text
+        )
+    } else {
+        text.to_string()
+    }
+}
+
+/// Resolves when a headless run is asked to stop: on Ctrl+C or, on unix,
+/// the SIGTERM that process supervisors send.
+async fn shutdown_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+async fn run(cli: &Cli) -> Result<()> {
+    match &cli.command {
+        Some(Commands::Schedule { file }) => run_timer(cli, Some(&Schedule::load(file)?)).await,
+        Some(Commands::Log) => history::print_log(&get_history_path(&cli.state_path()), unix_now()),
+        Some(Commands::Stats { by_type, since }) => {
+            let state_path = cli.state_path();
+            history::print_stats(&get_history_path(&state_path), unix_now(), *by_type, *since)?;
+            // XP is kept in the timer state rather than the history
+            let xp = load_state(&state_path).xp;
+            if xp > 0 {
+                println!();
+                println!("{}", format_xp(xp));
+            }
+            Ok(())
+        }
+        Some(Commands::Replay { date }) => {
+            history::print_replay(&get_history_path(&cli.state_path()), *date)
+        }
+        Some(Commands::Status { json }) => {
+            print_status(&cli.state_path(), *json);
+            Ok(())
+        }
+        None => run_timer(cli, None).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // Set up Ctrl+C handler
+    let original_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let _ = disable_raw_mode();
+        original_hook(panic_info);
+    }));
+
+    if let Err(e) = run(&cli).await {
+        disable_raw_mode()?;
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_state_default() {
+        let state = TimerState::default();
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.remaining_seconds, 25 * 60);
+        assert!(!state.is_paused);
+        assert!(state.last_update.is_none());
+    }
+
+    #[test]
+    fn test_timer_phase_durations() {
+        assert_eq!(TimerState::work_duration(), 25 * 60);
+        assert_eq!(TimerState::break_duration(), 5 * 60);
+    }
+
+    #[test]
+    fn test_timer_state_format_time() {
+        let mut state = TimerState {
+            remaining_seconds: 25 * 60,
+            ..Default::default()
+        };
+
+        // Test 25:00
         assert_eq!(state.format_time(), "25:00");
 
-        // Test 01:30
-        state.remaining_seconds = 90;
-        assert_eq!(state.format_time(), "01:30");
+        // Test 01:30
+        state.remaining_seconds = 90;
+        assert_eq!(state.format_time(), "01:30");
+
+        // Test 00:05
+        state.remaining_seconds = 5;
+        assert_eq!(state.format_time(), "00:05");
+
+        // Test 00:00
+        state.remaining_seconds = 0;
+        assert_eq!(state.format_time(), "00:00");
+    }
+
+    #[test]
+    fn test_timer_phase_emoji() {
+        let mut state = TimerState {
+            phase: TimerPhase::Work,
+            ..Default::default()
+        };
+        assert_eq!(state.emoji(), "🍅");
+
+        state.phase = TimerPhase::Break;
+        assert_eq!(state.emoji(), "🌴");
+
+        state.phase = TimerPhase::LongBreak;
+        assert_eq!(state.emoji(), "☕");
+    }
+
+    #[test]
+    fn test_timer_state_reset() {
+        // Modify state
+        let mut state = TimerState {
+            remaining_seconds: 100,
+            is_paused: true,
+            last_update: Some(123456),
+            ..Default::default()
+        };
+
+        // Reset to work
+        state.reset_to_work();
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.remaining_seconds, TimerState::work_duration());
+        assert!(!state.is_paused);
+        assert!(state.last_update.is_none());
+
+        // Reset to break
+        state.reset_to_break();
+        assert_eq!(state.phase, TimerPhase::Break);
+        assert_eq!(state.remaining_seconds, TimerState::break_duration());
+        assert!(!state.is_paused);
+        assert!(state.last_update.is_none());
+    }
+
+    #[test]
+    fn test_timer_state_is_finished() {
+        let mut state = TimerState {
+            remaining_seconds: 100,
+            ..Default::default()
+        };
+        assert!(!state.is_finished());
+
+        state.remaining_seconds = 0;
+        assert!(state.is_finished());
+    }
+
+    #[test]
+    fn test_update_survives_clock_going_backwards() {
+        let mut state = TimerState {
+            remaining_seconds: 600,
+            last_update: Some(2_000),
+            ..Default::default()
+        };
+
+        state.update_at(1_000);
+        assert_eq!(state.remaining_seconds, 600);
+        assert_eq!(state.last_update, Some(1_000));
+
+        // Counting resumes from the corrected clock
+        state.update_at(1_060);
+        assert_eq!(state.remaining_seconds, 540);
+    }
+
+    #[test]
+    fn test_status_line() {
+        let mut state = TimerState {
+            remaining_seconds: 24 * 60 + 13,
+            ..Default::default()
+        };
+        assert_eq!(state.status_line(), "🍅 24:13");
+
+        state.toggle_pause();
+        assert_eq!(state.status_line(), "⏸ 24:13");
+
+        state.toggle_pause();
+        state.phase = TimerPhase::Break;
+        assert_eq!(state.status_line(), "🌴 24:13");
+
+        assert_eq!(
+            state.status_json().to_string(),
+            r#"{"formatted":"24:13","is_paused":false,"phase":"Break","remaining_seconds":1453}"#
+        );
+    }
+
+    #[test]
+    fn test_timer_state_toggle_pause() {
+        let mut state = TimerState::default();
+
+        assert!(!state.is_paused);
+        state.toggle_pause();
+        assert!(state.is_paused);
+        state.toggle_pause();
+        assert!(!state.is_paused);
+    }
+
+    #[test]
+    fn test_time_paused_is_never_deducted() {
+        let mut state = TimerState {
+            remaining_seconds: 600,
+            ..Default::default()
+        };
+        state.update_at(1_000);
+        state.toggle_pause();
+        state.update_at(1_100);
+
+        // Reload the saved state long after, then resume
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state: TimerState = serde_json::from_str(&json).unwrap();
+        state.toggle_pause();
+        assert_eq!(state.last_update, None);
+        state.update_at(1_500);
+        assert_eq!(state.remaining_seconds, 600);
+
+        state.update_at(1_510);
+        assert_eq!(state.remaining_seconds, 590);
+    }
+
+    #[test]
+    fn test_banked_breaks() {
+        let cli = Cli::try_parse_from(["pomo", "--bank-breaks"]).unwrap();
+        let stash_path = std::env::temp_dir().join("pomo-bank-test-stash.json");
+        let mut state = TimerState::default();
+
+        // Work time is never banked
+        state.bank_break();
+        assert_eq!(state.banked_break_seconds, 0);
+        assert!(!state.cash_in_break());
+
+        // Skipping a break three minutes in banks the two minutes left
+        state.reset_to_break();
+        state.remaining_seconds = 2 * 60;
+        assert!(advance(&mut state, &cli, &stash_path, None));
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.banked_break_seconds, 2 * 60);
+
+        // So does skipping a break from the notification before it starts
+        assert!(skip_break(&mut state, &cli, None));
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(
+            state.banked_break_seconds,
+            2 * 60 + TimerState::break_duration()
+        );
+
+        // The bank is capped and survives a restart
+        state.reset_to_long_break();
+        state.bank_break();
+        state.reset_to_long_break();
+        state.bank_break();
+        assert_eq!(state.banked_break_seconds, MAX_BANKED_BREAK_SECONDS);
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state: TimerState = serde_json::from_str(&json).unwrap();
+
+        // Cashing in lengthens the current break and empties the bank
+        state.reset_to_break();
+        assert!(state.cash_in_break());
+        assert_eq!(
+            state.remaining_seconds,
+            TimerState::break_duration() + MAX_BANKED_BREAK_SECONDS
+        );
+        assert_eq!(state.progress_ratio(), 0.0);
+        assert_eq!(state.banked_break_seconds, 0);
+        assert!(!state.cash_in_break());
+
+        // Without --bank-breaks skipping banks nothing
+        let cli = Cli::try_parse_from(["pomo"]).unwrap();
+        state.remaining_seconds = 60;
+        advance(&mut state, &cli, &stash_path, None);
+        assert_eq!(state.banked_break_seconds, 0);
+    }
+
+    #[test]
+    fn test_skipped_work_counts_as_completed() {
+        let cli = Cli::try_parse_from(["pomo", "--gamify"]).unwrap();
+        let mut state = TimerState {
+            long_break_every: 2,
+            ..Default::default()
+        };
+
+        let history_path = test_history_path("skip");
+        let _ = fs::remove_file(&history_path);
+
+        state.remaining_seconds = 10 * 60;
+        record_work_session(&mut state, &cli, &history_path);
+        assert_eq!(state.completed_today, 1);
+        state.reset_to_next_break();
+        assert_eq!(state.phase, TimerPhase::Break);
+
+        // Skipping a break doesn't count as work
+        record_work_session(&mut state, &cli, &history_path);
+        assert_eq!(state.completed_today, 1);
+
+        // Skipping a session that never started isn't a pomodoro
+        state.reset_to_work();
+        record_work_session(&mut state, &cli, &history_path);
+        assert_eq!(state.completed_today, 1);
+
+        state.remaining_seconds = 20 * 60;
+        record_work_session(&mut state, &cli, &history_path);
+        state.reset_to_next_break();
+        assert_eq!(state.phase, TimerPhase::LongBreak);
+
+        // The log holds only the time actually worked
+        let durations: Vec<u32> = history::load(&history_path)
+            .unwrap()
+            .iter()
+            .map(|record| record.duration_seconds)
+            .collect();
+        assert_eq!(durations, vec![15 * 60, 5 * 60]);
+        // Skipped sessions earn XP like finished ones, and the second one
+        // ends this two-session cycle
+        assert_eq!(state.xp, compute_points(1, false) + compute_points(1, true));
+        let _ = fs::remove_file(&history_path);
+    }
+
+    #[test]
+    fn test_restart_phase() {
+        let mut state = TimerState {
+            work_seconds: 50 * 60,
+            remaining_seconds: 100,
+            is_paused: true,
+            last_update: Some(123456),
+            ..Default::default()
+        };
+        state.restart_phase();
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.remaining_seconds, 50 * 60);
+        assert!(state.is_paused);
+        assert!(state.last_update.is_none());
+
+        // A break from a range restarts with the length picked for it
+        state.break_range = Some(BreakRange { min: 5, max: 30 });
+        state.reset_to_break();
+        let picked = state.remaining_seconds;
+        for _ in 0..20 {
+            state.remaining_seconds = 10;
+            state.restart_phase();
+            assert_eq!(state.phase, TimerPhase::Break);
+            assert_eq!(state.remaining_seconds, picked);
+        }
+        // and survives a save and reload
+        let mut state: TimerState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        state.remaining_seconds = 10;
+        state.restart_phase();
+        assert_eq!(state.remaining_seconds, picked);
+
+        state.phase = TimerPhase::LongBreak;
+        state.restart_phase();
+        assert_eq!(state.remaining_seconds, TimerState::long_break_duration());
+    }
+
+    #[test]
+    fn test_timer_state_adjust_minutes() {
+        let mut state = TimerState {
+            remaining_seconds: 90,
+            ..Default::default()
+        };
+
+        state.adjust_minutes(2);
+        assert_eq!(state.remaining_seconds, 210);
+
+        state.adjust_minutes(-1);
+        assert_eq!(state.remaining_seconds, 150);
+
+        // Subtracting past zero lands on 00:00
+        state.adjust_minutes(-5);
+        assert_eq!(state.remaining_seconds, 0);
+
+        // Adding stops at the cap
+        state.remaining_seconds = MAX_ADJUSTED_SECONDS - 30;
+        state.adjust_minutes(1);
+        assert_eq!(state.remaining_seconds, MAX_ADJUSTED_SECONDS);
+        state.adjust_minutes(i32::MAX);
+        assert_eq!(state.remaining_seconds, MAX_ADJUSTED_SECONDS);
+    }
+
+    #[test]
+    fn test_adjustment_burst_coalescing() {
+        let start = Instant::now();
+        let mut burst = AdjustmentBurst::new(3);
+
+        // Rapid presses accumulate until the cap is reached
+        assert_eq!(burst.press(1, start), 1);
+        assert_eq!(burst.press(1, start + Duration::from_millis(100)), 1);
+        assert_eq!(burst.press(1, start + Duration::from_millis(200)), 1);
+        assert_eq!(burst.press(1, start + Duration::from_millis(300)), 0);
+        assert_eq!(
+            burst.indicator(start + Duration::from_millis(300)),
+            Some("+3m".to_string())
+        );
+
+        // Pressing the opposite key within the burst nets out
+        assert_eq!(burst.press(-1, start + Duration::from_millis(400)), -1);
+        assert_eq!(
+            burst.indicator(start + Duration::from_millis(400)),
+            Some("+2m".to_string())
+        );
+
+        // After the window the indicator disappears and a fresh burst starts
+        let later = start + Duration::from_millis(400) + ADJUST_BURST_WINDOW;
+        assert_eq!(burst.indicator(later), None);
+        assert_eq!(burst.press(-1, later), -1);
+        assert_eq!(burst.indicator(later), Some("-1m".to_string()));
+    }
+
+    #[test]
+    fn test_commit_locks_work_only() {
+        let mut state = TimerState::default();
+        assert!(state.is_committed(true));
+        assert!(!state.is_committed(false));
+
+        state.reset_to_break();
+        assert!(!state.is_committed(true));
+
+        state.reset_to_interruption();
+        assert!(!state.is_committed(true));
+    }
+
+    #[test]
+    fn test_day_index_boundaries() {
+        // 2024-01-01T00:00:00Z
+        let midnight_utc = 1_704_067_200;
+        assert_eq!(
+            day_index(midnight_utc - 1, 0) + 1,
+            day_index(midnight_utc, 0)
+        );
+
+        // 23:00 the previous evening in UTC-5 is still the previous local day
+        let evening = midnight_utc + 4 * 60 * 60;
+        assert_eq!(
+            day_index(evening, -5 * 60 * 60),
+            day_index(midnight_utc - 1, 0)
+        );
+
+        // 20:00Z is already the next day in UTC+9
+        let late_utc = midnight_utc + 20 * 60 * 60;
+        assert_eq!(
+            day_index(late_utc, 9 * 60 * 60),
+            day_index(midnight_utc, 0) + 1
+        );
+    }
+
+    #[test]
+    fn test_warmup_applies_once_per_day() {
+        let mut state = TimerState {
+            warmup_seconds: Some(10 * 60),
+            ..Default::default()
+        };
+
+        state.reset_to_work_on(100);
+        assert_eq!(state.remaining_seconds, 10 * 60);
+
+        state.reset_to_work_on(100);
+        assert_eq!(state.remaining_seconds, TimerState::work_duration());
+
+        // A new day gets the warm-up again
+        state.reset_to_work_on(101);
+        assert_eq!(state.remaining_seconds, 10 * 60);
+
+        // Without a warm-up configured the regular duration is used
+        state.warmup_seconds = None;
+        state.reset_to_work_on(102);
+        assert_eq!(state.remaining_seconds, TimerState::work_duration());
+    }
+
+    #[test]
+    fn test_configured_durations_survive_restart() {
+        let mut state = TimerState::default();
+        state.configure_durations(Some(50), Some(BreakRange { min: 10, max: 10 }));
+        // A fresh work session picks up the new length right away
+        assert_eq!(state.remaining_seconds, 50 * 60);
 
-        // Test 00:05
-        state.remaining_seconds = 5;
-        assert_eq!(state.format_time(), "00:05");
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state: TimerState = serde_json::from_str(&json).unwrap();
+        state.configure_durations(None, None);
+        state.reset_to_break();
+        assert_eq!(state.remaining_seconds, 10 * 60);
+        state.reset_to_work();
+        assert_eq!(state.remaining_seconds, 50 * 60);
 
-        // Test 00:00
-        state.remaining_seconds = 0;
-        assert_eq!(state.format_time(), "00:00");
+        // A session already under way keeps its remaining time
+        state.remaining_seconds = 1234;
+        state.last_update = Some(1);
+        state.configure_durations(Some(30), None);
+        assert_eq!(state.remaining_seconds, 1234);
+        assert_eq!(state.work_seconds, 30 * 60);
+
+        // Lengths that would overflow once converted to seconds are refused
+        assert!(Cli::try_parse_from(["pomo", "--work", "80000000"]).is_err());
+        assert!(Cli::try_parse_from(["pomo", "--long-break", "80000000"]).is_err());
+        assert!(Cli::try_parse_from(["pomo", "--work", "0"]).is_err());
+        assert!(Cli::try_parse_from(["pomo", "--warmup", "80000000"]).is_err());
+        assert!(Cli::try_parse_from(["pomo", "--warmup", "0"]).is_err());
+        let cli = Cli::try_parse_from(["pomo", "--work", "240", "--long-break", "240"]).unwrap();
+        assert_eq!((cli.work, cli.long_break), (Some(240), Some(240)));
+
+        // Files saved before durations were configurable use the defaults
+        let legacy =
+            r#"{"phase":"Work","remaining_seconds":60,"is_paused":false,"last_update":null}"#;
+        let state: TimerState = serde_json::from_str(legacy).unwrap();
+        assert_eq!(state.work_seconds, TimerState::work_duration());
+        assert_eq!(
+            state.fixed_break_seconds(),
+            Some(TimerState::break_duration())
+        );
     }
 
     #[test]
-    fn test_timer_phase_emoji() {
+    fn test_long_break_every_n_sessions() {
         let mut state = TimerState::default();
+        state.configure_long_breaks(Some(3), Some(20));
 
-        state.phase = TimerPhase::Work;
-        assert_eq!(state.emoji(), "🍅");
+        let mut breaks = Vec::new();
+        for _ in 0..6 {
+            state.reset_to_work_on(100);
+            state.record_completed_work_on(100);
+            state.reset_to_next_break();
+            breaks.push((state.phase, state.remaining_seconds));
+        }
+        let short = (TimerPhase::Break, TimerState::break_duration());
+        let long = (TimerPhase::LongBreak, 20 * 60);
+        assert_eq!(breaks, vec![short, short, long, short, short, long]);
+
+        // Progress through the cycle survives a restart
+        state.record_completed_work_on(100);
+        let json = serde_json::to_string(&state).unwrap();
+        let state: TimerState = serde_json::from_str(&json).unwrap();
+        assert_eq!(state.completed_work_sessions, 1);
+        assert_eq!(state.long_break_every, 3);
+        assert_eq!(state.long_break_seconds, 20 * 60);
+    }
+
+    #[test]
+    fn test_countdown_ticks_once_per_wakeup() {
+        let mut state = TimerState::default();
+        let mut now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_300);
+        state.update_at(1_700_000_000);
+
+        assert_eq!(until_next_second(now), Duration::from_millis(705));
+        for expected in (1495..1500).rev() {
+            now += until_next_second(now);
+            let seconds = now.duration_since(UNIX_EPOCH).unwrap().as_secs();
+            state.update_at(seconds);
+            assert_eq!(state.remaining_seconds, expected);
+        }
+        // Waking just after a boundary waits almost the full second
+        assert_eq!(until_next_second(now), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_refresh_is_clamped() {
+        let refresh = |args: &[&str]| Cli::try_parse_from(args).unwrap().refresh;
+
+        assert_eq!(refresh(&["pomo"]), 1000);
+        assert_eq!(refresh(&["pomo", "--refresh", "500"]), 500);
+        assert_eq!(refresh(&["pomo", "--refresh", "1"]), 20);
+        assert_eq!(refresh(&["pomo", "--refresh", "5000"]), 5000);
+        assert_eq!(refresh(&["pomo", "--refresh", "600000"]), 60_000);
+        assert!(Cli::try_parse_from(["pomo", "--refresh", "fast"]).is_err());
+
+        let ms = Duration::from_millis;
+        let next_second = ms(300);
+        // The default wakes when the countdown changes, faster ones sooner
+        assert_eq!(idle_wait(next_second, ms(1000), Some(1500)), ms(300));
+        assert_eq!(idle_wait(next_second, ms(100), Some(1500)), ms(100));
+        // Coarser ones skip seconds, still waking on a tick
+        assert_eq!(idle_wait(next_second, ms(5000), Some(1500)), ms(4300));
+        assert_eq!(idle_wait(next_second, ms(5500), None), ms(4300));
+        // but not past the end of the phase
+        assert_eq!(idle_wait(next_second, ms(5000), Some(2)), ms(1300));
+        assert_eq!(idle_wait(next_second, ms(5000), Some(0)), ms(300));
+    }
+
+    #[test]
+    fn test_break_range_parsing() {
+        assert_eq!("5-8".parse(), Ok(BreakRange { min: 5, max: 8 }));
+        assert_eq!("10".parse(), Ok(BreakRange { min: 10, max: 10 }));
+        assert!("8-5".parse::<BreakRange>().is_err());
+        assert!("five".parse::<BreakRange>().is_err());
+        assert!("5-".parse::<BreakRange>().is_err());
+        assert!("0".parse::<BreakRange>().is_err());
+        assert!("0-5".parse::<BreakRange>().is_err());
+        assert!("80000000".parse::<BreakRange>().is_err());
+        assert!("5-80000000".parse::<BreakRange>().is_err());
+
+        let cli = Cli::try_parse_from(["pomo", "--break", "5-8"]).unwrap();
+        assert_eq!(cli.break_range, Some(BreakRange { min: 5, max: 8 }));
+    }
+
+    #[test]
+    fn test_random_break_within_bounds() {
+        let mut rng = fastrand::Rng::with_seed(42);
+        let mut state = TimerState {
+            break_range: Some(BreakRange { min: 5, max: 8 }),
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            state.reset_to_break_with(&mut rng);
+            assert_eq!(state.phase, TimerPhase::Break);
+            assert!((5 * 60..=8 * 60).contains(&state.remaining_seconds));
+            assert_eq!(state.remaining_seconds % 60, 0);
+        }
+    }
+
+    #[test]
+    fn test_title_formats() {
+        let mut state = TimerState {
+            remaining_seconds: 1453,
+            ..Default::default()
+        };
+        assert_eq!(state.title(TitleFormat::Exact), "🍅 24:13 - pomo");
+        assert_eq!(state.title(TitleFormat::Rounded), "(25) pomo");
+
+        // Rounded counts only drop once a whole minute has passed
+        for (seconds, expected) in [
+            (1500, "(25) pomo"),
+            (61, "(2) pomo"),
+            (60, "(1) pomo"),
+            (1, "(1) pomo"),
+            (0, "(0) pomo"),
+        ] {
+            state.remaining_seconds = seconds;
+            assert_eq!(state.title(TitleFormat::Rounded), expected);
+        }
 
         state.phase = TimerPhase::Break;
-        assert_eq!(state.emoji(), "🌴");
+        state.remaining_seconds = 300;
+        assert_eq!(state.title(TitleFormat::Exact), "🌴 05:00 - pomo");
+
+        let title = |args: &[&str]| Cli::try_parse_from(args).unwrap().title();
+        assert_eq!(title(&["pomo"]), None);
+        assert_eq!(title(&["pomo", "--set-title"]), Some(TitleFormat::Exact));
+        assert_eq!(
+            title(&["pomo", "--title-format", "rounded"]),
+            Some(TitleFormat::Rounded)
+        );
     }
 
     #[test]
-    fn test_timer_state_reset() {
+    fn test_double_quit_window() {
+        let start = Instant::now();
+        let mut quit = QuitConfirm::default();
+
+        // A single press only arms the prompt
+        assert!(!quit.is_pending(start));
+        assert!(!quit.press(start));
+        assert!(quit.is_pending(start + Duration::from_millis(500)));
+
+        // A second press inside the window quits
+        assert!(quit.press(start + Duration::from_millis(500)));
+
+        // A second press after the window just re-arms it
+        let mut quit = QuitConfirm::default();
+        assert!(!quit.press(start));
+        let late = start + DOUBLE_QUIT_WINDOW;
+        assert!(!quit.is_pending(late));
+        assert!(!quit.press(late));
+        assert!(quit.press(late + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_synced_position() {
+        let anchor = 1_700_000_000;
+        let (work, rest) = (25 * 60, 5 * 60);
+
+        assert_eq!(
+            synced_position(anchor, anchor, work, rest),
+            (TimerPhase::Work, work)
+        );
+        assert_eq!(
+            synced_position(anchor, anchor + 60, work, rest),
+            (TimerPhase::Work, work - 60)
+        );
+        assert_eq!(
+            synced_position(anchor, anchor + work as u64, work, rest),
+            (TimerPhase::Break, rest)
+        );
+        assert_eq!(
+            synced_position(anchor, anchor + 28 * 60, work, rest),
+            (TimerPhase::Break, 2 * 60)
+        );
+
+        // The schedule repeats every cycle
+        let cycle = (work + rest) as u64;
+        assert_eq!(
+            synced_position(anchor, anchor + 3 * cycle + 90, work, rest),
+            (TimerPhase::Work, work - 90)
+        );
+
+        // Before the anchor the same cycle is followed backwards
+        assert_eq!(
+            synced_position(anchor, anchor - 60, work, rest),
+            (TimerPhase::Break, 60)
+        );
+    }
+
+    #[test]
+    fn test_sync_anchor_parsing() {
+        assert_eq!(parse_sync_anchor("1700000000"), Ok(1_700_000_000));
+        assert!(parse_sync_anchor("09:30").is_ok());
+        assert!(parse_sync_anchor("24:00").is_err());
+        assert!(parse_sync_anchor("soon").is_err());
+
+        // 09:30 local in UTC+9 on 2024-01-01 is 00:30Z
+        let now = 1_704_067_200 + 3 * 60 * 60;
+        assert_eq!(
+            local_clock_time(now, 9 * 60 * 60, 9 * 3600 + 30 * 60),
+            1_704_067_200 + 30 * 60
+        );
+    }
+
+    #[test]
+    fn test_clean_exit_flag() {
+        // After a clean exit the timer keeps running
         let mut state = TimerState::default();
+        assert!(!state.begin_run(true));
+        assert!(!state.is_paused);
+        assert!(!state.clean_exit);
 
-        // Modify state
-        state.remaining_seconds = 100;
-        state.is_paused = true;
-        state.last_update = Some(123456);
+        // The flag is still cleared when the next run starts, so it reads as a crash
+        let mut crashed: TimerState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert!(crashed.begin_run(true));
+        assert!(crashed.is_paused);
 
-        // Reset to work
-        state.reset_to_work();
-        assert_eq!(state.phase, TimerPhase::Work);
-        assert_eq!(state.remaining_seconds, TimerState::work_duration());
+        // Without the option a crash is detected but the timer isn't paused
+        let mut state = TimerState {
+            clean_exit: false,
+            ..Default::default()
+        };
+        assert!(state.begin_run(false));
+        assert!(!state.is_paused);
+
+        // Old state files without the flag count as a clean exit
+        let legacy: TimerState = serde_json::from_str(
+            r#"{"phase":"Work","remaining_seconds":60,"is_paused":false,"last_update":null}"#,
+        )
+        .unwrap();
+        assert!(legacy.clean_exit);
+    }
+
+    #[test]
+    fn test_quiet_resumes_paused_timer() {
+        let mut state = TimerState {
+            is_paused: true,
+            last_update: Some(1),
+            ..Default::default()
+        };
+        assert!(state.resume());
         assert!(!state.is_paused);
+        assert_eq!(state.last_update, None);
+        assert!(!state.resume());
+        assert!(!state.is_paused);
+
+        // Pausing after a crash would leave a headless run stuck
+        assert!(Cli::try_parse_from(["pomo", "--quiet", "--pause-after-crash"]).is_err());
+    }
+
+    #[test]
+    fn test_timestamps_prefix() {
+        let now = 1_710_390_609;
+        let cli = Cli::try_parse_from(["pomo", "--quiet"]).unwrap();
+        assert_eq!(output_line(&cli, "Work complete!", now), "Work complete!");
+
+        let cli = Cli::try_parse_from(["pomo", "--quiet", "--timestamps"]).unwrap();
+        assert_eq!(
+            output_line(&cli, "Work complete!", now),
+            format!(
+                "{} Work complete!",
+                history::format_iso8601(now, local_utc_offset(now))
+            )
+        );
+        assert_eq!(history::format_iso8601(now, 0), "2024-03-14T04:30:09+00:00");
+        assert_eq!(
+            history::format_iso8601(now, -5 * 3600),
+            "2024-03-13T23:30:09-05:00"
+        );
+        assert_eq!(
+            history::format_iso8601(now, 5 * 3600 + 45 * 60),
+            "2024-03-14T10:15:09+05:45"
+        );
+        // Only headless runs print lines to prefix
+        assert!(Cli::try_parse_from(["pomo", "--timestamps"]).is_err());
+    }
+
+    #[test]
+    fn test_daily_limit_survives_restart() {
+        let mut state = TimerState::default();
+        state.record_completed_work_on(20);
+        state.record_completed_work_on(20);
+        state.reset_to_work_on(20);
+        state.last_update = Some(1234567890);
+
+        assert!(!state.hold_for_daily_limit(20, Some(3)));
+        assert!(!state.hold_for_daily_limit(20, None));
+        assert!(state.hold_for_daily_limit(20, Some(2)));
         assert!(state.last_update.is_none());
 
-        // Reset to break
-        state.reset_to_break();
+        // A restart reads the persisted count and keeps holding
+        let mut restarted: TimerState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert!(restarted.hold_for_daily_limit(20, Some(2)));
+
+        // Breaks aren't held, and the next day work can start again
+        restarted.reset_to_break();
+        assert!(!restarted.hold_for_daily_limit(20, Some(2)));
+        restarted.reset_to_work_on(21);
+        assert!(!restarted.hold_for_daily_limit(21, Some(2)));
+    }
+
+    #[test]
+    fn test_stash_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("pomo-stash-test-{}.json", std::process::id()));
+        let mut state = TimerState {
+            phase: TimerPhase::Break,
+            remaining_seconds: 123,
+            last_update: Some(1234567890),
+            ..Default::default()
+        };
+
+        stash_state(&path, &state).unwrap();
+        state.reset_to_interruption();
+        assert_eq!(state.phase, TimerPhase::Interruption);
+        assert_eq!(state.remaining_seconds, TimerState::interruption_duration());
+
+        restore_from_stash(&mut state, &path);
         assert_eq!(state.phase, TimerPhase::Break);
-        assert_eq!(state.remaining_seconds, TimerState::break_duration());
-        assert!(!state.is_paused);
+        assert_eq!(state.remaining_seconds, 123);
+        // Time spent stashed must not count against the restored session
         assert!(state.last_update.is_none());
+        assert!(!path.exists());
+
+        // Settings and the label changed during the interruption stay
+        let mut state = TimerState {
+            remaining_seconds: 600,
+            interruptions: 2,
+            label: Some("write report".to_string()),
+            ..Default::default()
+        };
+        stash_state(&path, &state).unwrap();
+        state.reset_to_interruption();
+        state.interruptions = 0;
+        state.label = Some("review".to_string());
+        state.configure_durations(Some(50), None);
+        restore_from_stash(&mut state, &path);
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.remaining_seconds, 600);
+        assert_eq!(state.phase_seconds, 25 * 60);
+        assert_eq!(state.interruptions, 2);
+        assert_eq!(state.work_seconds, 50 * 60);
+        assert_eq!(state.label.as_deref(), Some("review"));
+
+        // A missing stash falls back to a fresh work session
+        state.reset_to_interruption();
+        restore_from_stash(&mut state, &path);
+        assert_eq!(state.phase, TimerPhase::Work);
+    }
+
+    struct StubRunner {
+        calls: std::cell::RefCell<Vec<String>>,
+        succeed_on: usize,
+    }
+
+    impl CommandRunner for StubRunner {
+        fn run(&self, program: &str, _args: &[&str]) -> io::Result<()> {
+            let mut calls = self.calls.borrow_mut();
+            calls.push(program.to_string());
+            if calls.len() > self.succeed_on {
+                Ok(())
+            } else {
+                Err(io::Error::other("unavailable"))
+            }
+        }
     }
 
     #[test]
-    fn test_timer_state_is_finished() {
+    fn test_lock_screen_tries_commands_in_order() {
+        let runner = StubRunner {
+            calls: Default::default(),
+            succeed_on: 0,
+        };
+        assert!(lock_screen(&runner));
+        assert_eq!(*runner.calls.borrow(), vec![LOCK_COMMANDS[0].0]);
+
+        // Nothing available is reported but not fatal
+        let runner = StubRunner {
+            calls: Default::default(),
+            succeed_on: usize::MAX,
+        };
+        assert!(!lock_screen(&runner));
+        assert_eq!(runner.calls.borrow().len(), LOCK_COMMANDS.len());
+    }
+
+    #[test]
+    fn test_notify_timeout_is_plumbed_into_notification() {
+        let timeout = |args: &[&str]| Cli::try_parse_from(args).unwrap().notify_timeout;
+        assert_eq!(timeout(&["pomo"]), Timeout::Default);
+        assert_eq!(
+            timeout(&["pomo", "--notify-timeout", "never"]),
+            Timeout::Never
+        );
+
+        let timeout = timeout(&["pomo", "--notify-timeout", "3000"]);
+        let notification = notifier::build_notification("Done", timeout);
+        assert_eq!(notification.timeout, Timeout::Milliseconds(3000));
+        assert_eq!(notification.body, "Done");
+        assert!(Cli::try_parse_from(["pomo", "--notify-timeout", "soon"]).is_err());
+    }
+
+    #[test]
+    fn test_interruption_counter() {
         let mut state = TimerState::default();
 
-        state.remaining_seconds = 100;
-        assert!(!state.is_finished());
+        state.mark_interruption();
+        state.mark_interruption();
+        assert_eq!(state.interruptions, 2);
 
-        state.remaining_seconds = 0;
-        assert!(state.is_finished());
+        // Breaks don't count distractions
+        state.reset_to_break();
+        state.mark_interruption();
+        assert_eq!(state.interruptions, 2);
+
+        // Each work phase starts from zero
+        state.reset_to_work();
+        assert_eq!(state.interruptions, 0);
+        state.mark_interruption();
+        assert_eq!(state.interruptions, 1);
     }
 
     #[test]
-    fn test_timer_state_toggle_pause() {
+    fn test_session_tagging() {
         let mut state = TimerState::default();
+        assert!(state.tag.is_none());
 
-        assert!(!state.is_paused);
-        state.toggle_pause();
-        assert!(state.is_paused);
-        state.toggle_pause();
-        assert!(!state.is_paused);
+        state.toggle_tag(SessionTag::Deep);
+        assert_eq!(state.tag, Some(SessionTag::Deep));
+        state.toggle_tag(SessionTag::Light);
+        assert_eq!(state.tag, Some(SessionTag::Light));
+        // Pressing the same key again clears the tag
+        state.toggle_tag(SessionTag::Light);
+        assert!(state.tag.is_none());
+
+        // Breaks can't be tagged, and each work phase starts untagged
+        state.toggle_tag(SessionTag::Deep);
+        state.reset_to_break();
+        state.toggle_tag(SessionTag::Light);
+        assert_eq!(state.tag, Some(SessionTag::Deep));
+        state.reset_to_work();
+        assert!(state.tag.is_none());
+    }
+
+    #[test]
+    fn test_compute_points() {
+        // First session with no streak
+        assert_eq!(compute_points(1, false), 10);
+        // Streak bonus grows with consecutive days
+        assert_eq!(compute_points(3, false), 12);
+        // Completing a full cycle earns a bonus
+        assert_eq!(compute_points(1, true), 30);
+        assert_eq!(compute_points(2, true), 31);
+        // Streak bonus is capped
+        assert_eq!(compute_points(100, false), 20);
+        assert_eq!(compute_points(0, false), 10);
+
+        // The cycle follows --long-break-every rather than a fixed four
+        let mut state = TimerState {
+            long_break_every: 3,
+            ..Default::default()
+        };
+        let cycle_ends: Vec<u32> = (1..=8)
+            .filter(|_| {
+                state.record_completed_work_on(0);
+                let completes_cycle = state.long_break_due();
+                state.reset_to_next_break();
+                completes_cycle
+            })
+            .collect();
+        assert_eq!(cycle_ends, vec![3, 6]);
+    }
+
+    #[test]
+    fn test_record_completed_work_streak() {
+        let mut state = TimerState::default();
+
+        state.record_completed_work_on(10);
+        state.record_completed_work_on(10);
+        assert_eq!(state.completed_today, 2);
+        assert_eq!(state.streak_days, 1);
+
+        // The next day continues the streak and restarts the daily count
+        state.record_completed_work_on(11);
+        assert_eq!(state.completed_today, 1);
+        assert_eq!(state.streak_days, 2);
+
+        // Skipping a day breaks the streak
+        state.record_completed_work_on(13);
+        assert_eq!(state.completed_today, 1);
+        assert_eq!(state.streak_days, 1);
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        let mut state = TimerState {
+            phase_seconds: 100,
+            remaining_seconds: 60,
+            ..Default::default()
+        };
+        assert_eq!(state.progress_ratio(), 0.4);
+        assert_eq!(
+            format_progress_bar(state.progress_ratio(), 17),
+            "[████░░░░░░] 40%"
+        );
+
+        // Wide terminals cap the bar, narrow ones shrink it
+        assert_eq!(
+            format_progress_bar(0.5, 200)
+                .chars()
+                .filter(|c| *c == '█')
+                .count(),
+            MAX_PROGRESS_WIDTH / 2
+        );
+        assert_eq!(format_progress_bar(0.5, 3), "[] 50%");
+
+        // Ratios within one step draw the same bar
+        let bars: Vec<String> = [0.40, 0.412, 0.43, 0.449]
+            .into_iter()
+            .map(|ratio| format_progress_bar(quantize_ratio(ratio, Some(5)), 47))
+            .collect();
+        assert!(bars.iter().all(|bar| *bar == bars[0]));
+        assert_eq!(bars[0], format_progress_bar(0.4, 47));
+        assert_eq!(
+            format_progress_bar(quantize_ratio(0.45, Some(5)), 47),
+            format_progress_bar(0.45, 47)
+        );
+        assert_eq!(quantize_ratio(0.43, None), 0.43);
+        assert!(format_progress_bar(quantize_ratio(0.295, Some(1)), 47).ends_with(" 29%"));
+        assert!(format_progress_bar(quantize_ratio(0.9, Some(3)), 47).ends_with(" 90%"));
+
+        // Time added mid-phase grows the total rather than rewinding progress
+        state.adjust_minutes(1);
+        assert_eq!(state.phase_seconds, 160);
+        assert_eq!(state.elapsed_seconds(), 40);
+
+        state.reset_to_break();
+        assert_eq!(state.progress_ratio(), 0.0);
+        state.remaining_seconds = 0;
+        assert_eq!(
+            format_progress_bar(state.progress_ratio(), 17),
+            "[██████████] 100%"
+        );
+    }
+
+    #[test]
+    fn test_format_xp() {
+        assert_eq!(format_xp(0), "Lv 1 [░░░░░░░░░░] 0/100 XP");
+        assert_eq!(format_xp(160), "Lv 2 [██████░░░░] 60/100 XP");
+    }
+
+    #[test]
+    fn test_phase_dwell_timing() {
+        let start = Instant::now();
+        let dwell = PhaseDwell::new(TimerPhase::Work, start, Duration::from_secs(2));
+
+        assert!(!dwell.is_over(start));
+        assert!(!dwell.is_over(start + Duration::from_millis(1999)));
+        assert!(dwell.is_over(start + Duration::from_secs(2)));
+        assert_eq!(dwell.message(), "Work complete! Break starting…");
+
+        // A zero dwell moves on immediately, like the old behavior
+        let dwell = PhaseDwell::new(TimerPhase::Break, start, Duration::ZERO);
+        assert!(dwell.is_over(start));
+
+        // A manual dwell waits for Space however long it takes
+        let dwell = PhaseDwell::manual(TimerPhase::Work);
+        assert!(!dwell.is_over(start + Duration::from_secs(24 * 60 * 60)));
+        assert!(dwell.message().contains("press space to start your break"));
+        assert_eq!(space_action(Some(&dwell)), SpaceAction::StartNextPhase);
+    }
+
+    #[test]
+    fn test_label_edit() {
+        let mut edit = LabelEdit::new(Some("wrte"));
+        assert_eq!(edit.key(KeyCode::Backspace), LabelEditStep::Editing);
+        assert_eq!(edit.key(KeyCode::Backspace), LabelEditStep::Editing);
+        for c in "ite report ".chars() {
+            edit.key(KeyCode::Char(c));
+        }
+        assert!(edit.prompt().starts_with("Task: write report _"));
+        assert_eq!(
+            edit.key(KeyCode::Enter),
+            LabelEditStep::Save(Some("write report".to_string()))
+        );
+
+        // An empty label clears it, Esc leaves it alone
+        let mut edit = LabelEdit::new(None);
+        edit.key(KeyCode::Char(' '));
+        assert_eq!(edit.key(KeyCode::Enter), LabelEditStep::Save(None));
+        assert_eq!(
+            LabelEdit::new(None).key(KeyCode::Esc),
+            LabelEditStep::Cancel
+        );
+    }
+
+    #[test]
+    fn test_space_action_dispatch() {
+        assert_eq!(space_action(None), SpaceAction::TogglePause);
+
+        let dwell = PhaseDwell::new(TimerPhase::Work, Instant::now(), Duration::from_secs(2));
+        assert_eq!(space_action(Some(&dwell)), SpaceAction::StartNextPhase);
+    }
+
+    #[derive(Default)]
+    struct MockNotifier {
+        events: Arc<std::sync::Mutex<Vec<TransitionEvent>>>,
+        unavailable: bool,
+        /// Button "clicked" right away when one is offered.
+        action: Option<NotificationAction>,
+    }
+
+    impl Notifier for MockNotifier {
+        fn notify(&self, event: &TransitionEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+
+        fn notify_with_actions(
+            &self,
+            event: &TransitionEvent,
+        ) -> Option<mpsc::Receiver<NotificationAction>> {
+            self.notify(event);
+            let action = self.action?;
+            let (sender, receiver) = mpsc::channel();
+            sender.send(action).unwrap();
+            Some(receiver)
+        }
+
+        fn is_available(&self) -> bool {
+            !self.unavailable
+        }
+    }
+
+    fn test_history_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "pomo-main-history-test-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_finish_phase_notifies_every_channel() {
+        let cli = Cli::try_parse_from(["pomo"]).unwrap();
+        let history_path = test_history_path("notify");
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(MockNotifier {
+                events: events.clone(),
+                ..Default::default()
+            }),
+            Box::new(MockNotifier {
+                events: events.clone(),
+                ..Default::default()
+            }),
+        ];
+
+        let mut state = TimerState {
+            remaining_seconds: 0,
+            ..Default::default()
+        };
+        finish_phase(&mut state, &cli, &notifiers, &history_path);
+        let work_finished = TransitionEvent {
+            finished: TimerPhase::Work,
+            message: default_message(TimerPhase::Work).to_string(),
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![work_finished.clone(), work_finished]
+        );
+        assert_eq!(state.completed_today, 1);
+
+        // A finished phase is announced only once, even across a restart
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state: TimerState = serde_json::from_str(&json).unwrap();
+        finish_phase(&mut state, &cli, &notifiers, &history_path);
+        assert_eq!(events.lock().unwrap().len(), 2);
+        assert_eq!(state.completed_today, 1);
+
+        state.reset_to_break();
+        events.lock().unwrap().clear();
+        finish_phase(&mut state, &cli, &notifiers, &history_path);
+        let break_finished = TransitionEvent {
+            finished: TimerPhase::Break,
+            message: default_message(TimerPhase::Break).to_string(),
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![break_finished.clone(), break_finished]
+        );
+        let _ = fs::remove_file(&history_path);
+    }
+
+    #[test]
+    fn test_celebrate_completed_set() {
+        let history_path = test_history_path("celebrate");
+        let _ = fs::remove_file(&history_path);
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(MockNotifier {
+            events: events.clone(),
+            ..Default::default()
+        })];
+        let finish_set = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap();
+            let mut state = TimerState {
+                long_break_every: 2,
+                ..Default::default()
+            };
+            for _ in 0..2 {
+                state.reset_to_work();
+                state.remaining_seconds = 0;
+                finish_phase(&mut state, &cli, &notifiers, &history_path);
+            }
+            events.lock().unwrap().drain(..).collect::<Vec<_>>()
+        };
+
+        let sent = finish_set(&["pomo", "--celebrate"]);
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2].message, set_summary(2, 50 * 60));
+        // Nothing extra without the flag, or in a headless run
+        assert_eq!(finish_set(&["pomo"]).len(), 2);
+        assert_eq!(finish_set(&["pomo", "--celebrate", "--quiet"]).len(), 2);
+        let _ = fs::remove_file(&history_path);
+    }
+
+    #[test]
+    fn test_notification_actions() {
+        let cli = Cli::try_parse_from(["pomo"]).unwrap();
+        let history_path = test_history_path("actions");
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(MockNotifier::default()),
+            Box::new(MockNotifier {
+                action: Some(NotificationAction::SkipBreak),
+                ..Default::default()
+            }),
+        ];
+
+        // Only a finished work session offers buttons
+        let mut state = TimerState {
+            remaining_seconds: 0,
+            ..Default::default()
+        };
+        let response = finish_phase(&mut state, &cli, &notifiers, &history_path).unwrap();
+        assert_eq!(response.try_recv(), Ok(NotificationAction::SkipBreak));
+        state.reset_to_break();
+        assert!(finish_phase(&mut state, &cli, &notifiers, &history_path).is_none());
+
+        // Skipping the break goes straight back to a full work session
+        let mut state = TimerState {
+            remaining_seconds: 0,
+            ..Default::default()
+        };
+        assert!(skip_break(&mut state, &cli, None));
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.remaining_seconds, 25 * 60);
+
+        assert_eq!(
+            NotificationAction::from_id(NotificationAction::StartBreak.id()),
+            Some(NotificationAction::StartBreak)
+        );
+        assert_eq!(NotificationAction::from_id("__closed"), None);
+        let _ = fs::remove_file(&history_path);
+    }
+
+    #[test]
+    fn test_notification_message_placeholders() {
+        let cli = Cli::try_parse_from([
+            "pomo",
+            "--work-message",
+            "Nice! {today_count} done today, {streak}-day streak. Take a {next_phase}. {oops}",
+        ])
+        .unwrap();
+        let history_path = test_history_path("placeholders");
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(MockNotifier {
+            events: events.clone(),
+            ..Default::default()
+        })];
+
+        let mut state = TimerState {
+            remaining_seconds: 0,
+            ..Default::default()
+        };
+        state.record_completed_work_on(local_day(unix_now()));
+        state.record_completed_work_on(local_day(unix_now()));
+        finish_phase(&mut state, &cli, &notifiers, &history_path);
+        assert_eq!(
+            events.lock().unwrap()[0].message,
+            "Nice! 3 done today, 1-day streak. Take a break. {oops}"
+        );
+
+        // Phases without a template keep the default message
+        state.reset_to_break();
+        finish_phase(&mut state, &cli, &notifiers, &history_path);
+        assert_eq!(
+            events.lock().unwrap()[1].message,
+            default_message(TimerPhase::Break)
+        );
+        let _ = fs::remove_file(&history_path);
+    }
+
+    #[test]
+    fn test_notification_availability_warning() {
+        let available: Vec<Box<dyn Notifier>> =
+            vec![Box::new(MockNotifier::default()), Box::new(BellNotifier)];
+        assert_eq!(availability_warning(&available), None);
+        assert_eq!(availability_warning(&[]), None);
+
+        let unavailable: Vec<Box<dyn Notifier>> = vec![Box::new(MockNotifier {
+            unavailable: true,
+            ..Default::default()
+        })];
+        assert_eq!(
+            availability_warning(&unavailable),
+            Some("Notifications unavailable; consider --bell")
+        );
+    }
+
+    #[test]
+    fn test_phase_hooks() {
+        let cli =
+            Cli::try_parse_from(["pomo", "--on-work", "dnd on", "--on-break", "dnd off"]).unwrap();
+        assert_eq!(cli.hook_for(TimerPhase::Work), Some("dnd on"));
+        assert_eq!(cli.hook_for(TimerPhase::Break), Some("dnd off"));
+        // Long breaks fall back to the break hook
+        assert_eq!(cli.hook_for(TimerPhase::LongBreak), Some("dnd off"));
+        assert_eq!(cli.hook_for(TimerPhase::Interruption), None);
+
+        let cli = Cli::try_parse_from(["pomo", "--on-break", "a", "--on-long-break", "b"]).unwrap();
+        assert_eq!(cli.hook_for(TimerPhase::LongBreak), Some("b"));
+        assert_eq!(cli.hook_for(TimerPhase::Work), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_hook_passes_phase() {
+        let path = std::env::temp_dir().join(format!("pomo-hook-test-{}.txt", std::process::id()));
+        let command = format!("echo \"$1 $POMO_PHASE\" > '{}'", path.display());
+
+        let status = spawn_hook(&command, TimerPhase::LongBreak)
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "long_break long_break\n"
+        );
+        let _ = fs::remove_file(&path);
+
+        let status = spawn_hook("exit 3", TimerPhase::Work)
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert_eq!(status.code(), Some(3));
+    }
+
+    #[test]
+    fn test_build_notifiers_from_flags() {
+        let count = |args: &[&str]| build_notifiers(&Cli::try_parse_from(args).unwrap()).len();
+        assert_eq!(count(&["pomo"]), 1);
+        assert_eq!(count(&["pomo", "--bell"]), 2);
+        assert_eq!(count(&["pomo", "--no-notify"]), 0);
+        assert_eq!(count(&["pomo", "--no-notify", "--bell"]), 1);
     }
 
     #[test]
     fn test_config_path() {
-        let path = get_config_path();
-        assert!(path.to_string_lossy().ends_with(".pomo.json"));
+        let home = std::env::temp_dir().join(format!("pomo-home-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let config_dir = home.join(".config");
+
+        let path = resolve_config_path(Some(home.clone()), Some(config_dir.clone()));
+        assert_eq!(path, config_dir.join("pomo/state.json"));
+        assert_eq!(
+            get_stash_path(&path),
+            config_dir.join("pomo/state-stash.json")
+        );
+        // The first save creates the directory
+        save_state(&path, &TimerState::default()).unwrap();
+        assert!(path.exists());
+
+        // Without any directories the state goes to the temp dir
+        assert_eq!(
+            resolve_config_path(None, None),
+            std::env::temp_dir().join(".pomo.json")
+        );
+
+        // An existing legacy state file keeps being used
+        fs::write(home.join(".pomo.json"), "{}").unwrap();
+        let path = resolve_config_path(Some(home.clone()), Some(config_dir));
+        assert_eq!(path, home.join(".pomo.json"));
+        assert_eq!(get_stash_path(&path), home.join(".pomo-stash.json"));
+        assert_eq!(get_history_path(&path), home.join(".pomo-history.jsonl"));
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn test_state_file_flag() {
+        let cli =
+            Cli::try_parse_from(["pomo", "status", "--state-file", "/tmp/focus.json"]).unwrap();
+        assert_eq!(cli.state_path(), PathBuf::from("/tmp/focus.json"));
+        assert_eq!(
+            get_stash_path(&cli.state_path()),
+            PathBuf::from("/tmp/focus-stash.json")
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("pomo-state-test-{}.json", std::process::id()));
+        let state = TimerState {
+            remaining_seconds: 42,
+            ..Default::default()
+        };
+        save_state(&path, &state).unwrap();
+        assert_eq!(load_state(&path).remaining_seconds, 42);
+        let _ = fs::remove_file(&path);
+        assert_eq!(load_state(&path).remaining_seconds, 25 * 60);
     }
 
     #[test]
@@ -347,6 +3477,7 @@ mod tests {
             remaining_seconds: 300,
             is_paused: true,
             last_update: Some(1234567890),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&state).unwrap();
@@ -0,0 +1,233 @@
+//! Notification channels fired when a phase finishes.
+
+use crate::{history::format_focus, TimerPhase};
+use notify_rust::{Notification, Timeout};
+use std::{
+    io::{self, Write},
+    sync::mpsc::Receiver,
+};
+
+/// A phase transition worth telling the user about.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TransitionEvent {
+    pub finished: TimerPhase,
+    pub message: String,
+}
+
+/// Buttons offered on the notification for a finished work session.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NotificationAction {
+    StartBreak,
+    SkipBreak,
+}
+
+impl NotificationAction {
+    pub const ALL: [NotificationAction; 2] = [
+        NotificationAction::StartBreak,
+        NotificationAction::SkipBreak,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            NotificationAction::StartBreak => "start-break",
+            NotificationAction::SkipBreak => "skip-break",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NotificationAction::StartBreak => "Start break",
+            NotificationAction::SkipBreak => "Skip break",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+}
+
+/// Notification body used when no custom message is configured.
+pub fn default_message(finished: TimerPhase) -> &'static str {
+    match finished {
+        TimerPhase::Work => "Work session completed! Time for a break.",
+        TimerPhase::Break => "Break time over! Ready for work?",
+        TimerPhase::LongBreak => "Long break over! Ready for work?",
+        TimerPhase::Interruption => "Interruption over! Back to your session.",
+    }
+}
+
+/// Congratulations sent under `--celebrate` when the last session of a set
+/// finishes, like "Set complete: 4 pomodoros, 1h 40m of focus. Enjoy your
+/// long break!".
+pub fn set_summary(sessions: u32, focus_seconds: u64) -> String {
+    format!(
+        "Set complete: {} pomodoro{}, {} of focus. Enjoy your long break!",
+        sessions,
+        if sessions == 1 { "" } else { "s" },
+        format_focus(focus_seconds)
+    )
+}
+
+/// Fills `{name}` placeholders from `values`. Unknown placeholders and
+/// unmatched braces are left as they are.
+pub fn render_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                rendered.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// A way of telling the user about transitions. Every enabled channel is
+/// notified, so adding a new one is just another implementation.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, event: &TransitionEvent);
+
+    /// Like `notify`, but offering the `NotificationAction` buttons. Returns
+    /// where the chosen action arrives, or None when the channel has no
+    /// buttons and only notified.
+    fn notify_with_actions(&self, event: &TransitionEvent) -> Option<Receiver<NotificationAction>> {
+        self.notify(event);
+        None
+    }
+
+    /// Whether the channel can deliver anything right now.
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+/// Warning to show at launch when an enabled channel can't deliver.
+pub fn availability_warning(notifiers: &[Box<dyn Notifier>]) -> Option<&'static str> {
+    notifiers
+        .iter()
+        .any(|notifier| !notifier.is_available())
+        .then_some("Notifications unavailable; consider --bell")
+}
+
+/// Desktop notifications via the platform notification service.
+pub struct DesktopNotifier {
+    pub timeout: Timeout,
+}
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, event: &TransitionEvent) {
+        let _ = build_notification(&event.message, self.timeout).show();
+    }
+
+    /// Only daemons that advertise the "actions" capability get buttons;
+    /// everything else falls back to the plain notification.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn notify_with_actions(&self, event: &TransitionEvent) -> Option<Receiver<NotificationAction>> {
+        let supports_actions = notify_rust::get_capabilities()
+            .is_ok_and(|capabilities| capabilities.iter().any(|c| c == "actions"));
+        if !supports_actions {
+            self.notify(event);
+            return None;
+        }
+        let mut notification = build_notification(&event.message, self.timeout);
+        for action in NotificationAction::ALL {
+            notification.action(action.id(), action.label());
+        }
+        let handle = notification.show().ok()?;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        // Waiting blocks until the notification is clicked or closed, so it
+        // gets its own thread; the timer stops listening after a few seconds
+        std::thread::spawn(move || {
+            handle.wait_for_action(|id| {
+                if let Some(action) = NotificationAction::from_id(id) {
+                    let _ = sender.send(action);
+                }
+            })
+        });
+        Some(receiver)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn is_available(&self) -> bool {
+        notify_rust::get_server_information().is_ok()
+    }
+}
+
+/// Rings the terminal bell, which works over SSH and without a notification daemon.
+pub struct BellNotifier;
+
+impl Notifier for BellNotifier {
+    fn notify(&self, _event: &TransitionEvent) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x07");
+        let _ = stdout.flush();
+    }
+}
+
+pub fn build_notification(message: &str, timeout: Timeout) -> Notification {
+    let mut notification = Notification::new();
+    notification
+        .summary("Pomodoro Timer")
+        .body(message)
+        .timeout(timeout);
+    notification
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let values = [
+            ("today_count", "3".to_string()),
+            ("streak", "2".to_string()),
+            ("next_phase", "break".to_string()),
+        ];
+
+        assert_eq!(
+            render_template(
+                "Nice! {today_count} done today. Take a {next_phase}.",
+                &values
+            ),
+            "Nice! 3 done today. Take a break."
+        );
+        assert_eq!(render_template("{streak}{streak} days", &values), "22 days");
+        // Unknown placeholders and stray braces stay literal
+        assert_eq!(
+            render_template("{unknown} {streak}", &values),
+            "{unknown} 2"
+        );
+        assert_eq!(render_template("{ {streak} }", &values), "{ 2 }");
+        assert_eq!(render_template("open {streak", &values), "open {streak");
+        assert_eq!(render_template("", &values), "");
+    }
+
+    #[test]
+    fn test_set_summary() {
+        assert_eq!(
+            set_summary(4, 100 * 60),
+            "Set complete: 4 pomodoros, 1h 40m of focus. Enjoy your long break!"
+        );
+        assert_eq!(
+            set_summary(1, 50 * 60 + 30),
+            "Set complete: 1 pomodoro, 50m of focus. Enjoy your long break!"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Optional auto-pause when a laptop goes on battery or its lid closes.
+//!
+//! Power state is read from sysfs/procfs on Linux. Everywhere else no reading
+//! is available and auto-pause does nothing.
+
+use crate::TimerState;
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// How often the power state is re-read.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PowerReading {
+    pub on_battery: bool,
+    pub lid_closed: bool,
+}
+
+impl PowerReading {
+    fn is_mobile(self) -> bool {
+        self.on_battery || self.lid_closed
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum PowerAction {
+    Pause,
+    Resume,
+    Nothing,
+}
+
+/// Pauses when the machine goes mobile, and resumes only pauses it made
+/// itself so a manual pause is never undone. Pausing happens on the change
+/// to mobile only, so resuming by hand on battery sticks.
+fn power_action(
+    reading: PowerReading,
+    was_mobile: bool,
+    is_paused: bool,
+    auto_paused: bool,
+) -> PowerAction {
+    let mobile = reading.is_mobile();
+    if mobile && !was_mobile && !is_paused {
+        PowerAction::Pause
+    } else if !mobile && is_paused && auto_paused {
+        PowerAction::Resume
+    } else {
+        PowerAction::Nothing
+    }
+}
+
+#[derive(Default)]
+pub struct PowerMonitor {
+    last_poll: Option<Instant>,
+    auto_paused: bool,
+    /// Whether the previous reading was mobile; nothing read yet counts as
+    /// plugged in, so starting on battery pauses.
+    was_mobile: bool,
+}
+
+impl PowerMonitor {
+    /// Re-reads the power state every few seconds and pauses or resumes.
+    pub fn poll(&mut self, state: &mut TimerState, now: Instant) {
+        if self
+            .last_poll
+            .is_some_and(|last| now.duration_since(last) < POLL_INTERVAL)
+        {
+            return;
+        }
+        self.last_poll = Some(now);
+        if let Some(reading) = read_power_state() {
+            self.apply(reading, state);
+        }
+    }
+
+    fn apply(&mut self, reading: PowerReading, state: &mut TimerState) {
+        let was_mobile = std::mem::replace(&mut self.was_mobile, reading.is_mobile());
+        match power_action(reading, was_mobile, state.is_paused, self.auto_paused) {
+            PowerAction::Pause => {
+                state.toggle_pause();
+                self.auto_paused = true;
+            }
+            PowerAction::Resume => {
+                state.toggle_pause();
+                self.auto_paused = false;
+            }
+            PowerAction::Nothing => {
+                // A manual resume or pause takes over from auto-pause
+                if !state.is_paused {
+                    self.auto_paused = false;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_power_state() -> Option<PowerReading> {
+    Some(PowerReading {
+        on_battery: on_battery(Path::new("/sys/class/power_supply"))?,
+        lid_closed: lid_closed(Path::new("/proc/acpi/button/lid")),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_power_state() -> Option<PowerReading> {
+    None
+}
+
+/// On battery when there is a mains adapter and none of them is online.
+/// Machines without a mains adapter entry (desktops) are never on battery.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn on_battery(power_supply_dir: &Path) -> Option<bool> {
+    let mut saw_mains = false;
+    for entry in fs::read_dir(power_supply_dir).ok()?.flatten() {
+        let path = entry.path();
+        let is_mains =
+            fs::read_to_string(path.join("type")).is_ok_and(|kind| kind.trim() == "Mains");
+        if !is_mains {
+            continue;
+        }
+        saw_mains = true;
+        if fs::read_to_string(path.join("online")).is_ok_and(|online| online.trim() == "1") {
+            return Some(false);
+        }
+    }
+    Some(saw_mains)
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn lid_closed(lid_dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(lid_dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        fs::read_to_string(entry.path().join("state")).is_ok_and(|state| state.contains("closed"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLUGGED_IN: PowerReading = PowerReading {
+        on_battery: false,
+        lid_closed: false,
+    };
+    const ON_BATTERY: PowerReading = PowerReading {
+        on_battery: true,
+        lid_closed: false,
+    };
+    const LID_CLOSED: PowerReading = PowerReading {
+        on_battery: false,
+        lid_closed: true,
+    };
+
+    #[test]
+    fn test_power_action() {
+        assert_eq!(
+            power_action(PLUGGED_IN, false, false, false),
+            PowerAction::Nothing
+        );
+        assert_eq!(
+            power_action(ON_BATTERY, false, false, false),
+            PowerAction::Pause
+        );
+        assert_eq!(
+            power_action(LID_CLOSED, false, false, false),
+            PowerAction::Pause
+        );
+        assert_eq!(
+            power_action(ON_BATTERY, true, true, true),
+            PowerAction::Nothing
+        );
+        assert_eq!(
+            power_action(PLUGGED_IN, true, true, true),
+            PowerAction::Resume
+        );
+        // Staying on battery never pauses again
+        assert_eq!(
+            power_action(ON_BATTERY, true, false, false),
+            PowerAction::Nothing
+        );
+        // A manual pause is left alone when power comes back
+        assert_eq!(
+            power_action(PLUGGED_IN, true, true, false),
+            PowerAction::Nothing
+        );
+    }
+
+    #[test]
+    fn test_monitor_pauses_and_resumes() {
+        let mut monitor = PowerMonitor::default();
+        let mut state = TimerState::default();
+
+        monitor.apply(ON_BATTERY, &mut state);
+        assert!(state.is_paused);
+        monitor.apply(ON_BATTERY, &mut state);
+        assert!(state.is_paused);
+        monitor.apply(PLUGGED_IN, &mut state);
+        assert!(!state.is_paused);
+
+        // Resuming by hand while on battery doesn't fight the user
+        monitor.apply(ON_BATTERY, &mut state);
+        assert!(state.is_paused);
+        state.toggle_pause();
+        monitor.apply(ON_BATTERY, &mut state);
+        assert!(!state.is_paused);
+        monitor.apply(ON_BATTERY, &mut state);
+        assert!(!state.is_paused);
+        monitor.apply(PLUGGED_IN, &mut state);
+        assert!(!state.is_paused);
+
+        // Going mobile again pauses again
+        monitor.apply(LID_CLOSED, &mut state);
+        assert!(state.is_paused);
+    }
+
+    #[test]
+    fn test_on_battery_from_sysfs() {
+        let dir = std::env::temp_dir().join(format!("pomo-power-test-{}", std::process::id()));
+        let adapter = dir.join("AC");
+        let battery = dir.join("BAT0");
+        fs::create_dir_all(&adapter).unwrap();
+        fs::create_dir_all(&battery).unwrap();
+        fs::write(adapter.join("type"), "Mains\n").unwrap();
+        fs::write(battery.join("type"), "Battery\n").unwrap();
+
+        fs::write(adapter.join("online"), "1\n").unwrap();
+        assert_eq!(on_battery(&dir), Some(false));
+        fs::write(adapter.join("online"), "0\n").unwrap();
+        assert_eq!(on_battery(&dir), Some(true));
+
+        fs::remove_dir_all(&adapter).unwrap();
+        assert_eq!(on_battery(&dir), Some(false));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(on_battery(&dir), None);
+    }
+}
@@ -0,0 +1,360 @@
+//! Optional JSON-RPC 2.0 control socket for editor and IDE integrations.
+//!
+//! Clients connect to the Unix-domain socket, write a single JSON-RPC request
+//! terminated by a newline and read back a single response line. Requests are
+//! serviced by `run_timer` between render frames, so they act on the live
+//! timer exactly like key presses do.
+
+use crate::{advance, record_work_session, schedule::Schedule, sibling_path, Cli, TimerState};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const COMMITTED: i64 = -32000;
+const SYNCED: i64 = -32001;
+
+/// How long a client may take to send its request before being dropped.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub fn get_socket_path(state_path: &Path) -> PathBuf {
+    sibling_path(state_path, ".sock")
+}
+
+/// What requests act on besides the timer state itself.
+pub struct RequestContext<'a> {
+    pub cli: &'a Cli,
+    pub schedule: Option<&'a Schedule>,
+    pub stash_path: &'a Path,
+    pub history_path: &'a Path,
+}
+
+pub struct RpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl RpcServer {
+    pub fn bind(path: &Path) -> Result<Self> {
+        // A socket nobody is listening on is left over from a previous run
+        if path.exists() && UnixStream::connect(path).is_err() {
+            let _ = fs::remove_file(path);
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed to bind control socket {}", path.display()))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Answers every pending connection without blocking the render loop.
+    /// Returns false once a skip has run past the last schedule block.
+    pub fn service(&self, state: &mut TimerState, context: &RequestContext) -> bool {
+        let mut running = true;
+        while let Ok((stream, _)) = self.listener.accept() {
+            if let Ok(false) = serve_connection(stream, state, context) {
+                running = false;
+            }
+        }
+        running
+    }
+}
+
+impl Drop for RpcServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn serve_connection(
+    stream: UnixStream,
+    state: &mut TimerState,
+    context: &RequestContext,
+) -> io::Result<bool> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let (response, running) = handle_request(state, context, &line);
+    writeln!(writer, "{}", response)?;
+    Ok(running)
+}
+
+/// Handles one raw request line and returns the JSON-RPC response object,
+/// along with false if a skip finished the schedule.
+fn handle_request(state: &mut TimerState, context: &RequestContext, line: &str) -> (Value, bool) {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(_) => {
+            return (
+                error_response(Value::Null, PARSE_ERROR, "Parse error"),
+                true,
+            )
+        }
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return (error_response(id, INVALID_REQUEST, "Invalid Request"), true);
+    };
+    let params = request.get("params").unwrap_or(&Value::Null);
+
+    if matches!(method, "skip" | "extend") && state.is_committed(context.cli.commit) {
+        return (
+            error_response(id, COMMITTED, "Work session is committed"),
+            true,
+        );
+    }
+    // The shared schedule would put the timer straight back
+    if method == "skip" && context.cli.sync.is_some() {
+        return (
+            error_response(id, SYNCED, "Skipping is disabled under --sync"),
+            true,
+        );
+    }
+    let mut running = true;
+    let response = match dispatch(state, context, method, params, &mut running) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_response(id, code, message),
+    };
+    (response, running)
+}
+
+fn dispatch(
+    state: &mut TimerState,
+    context: &RequestContext,
+    method: &str,
+    params: &Value,
+    running: &mut bool,
+) -> Result<Value, (i64, &'static str)> {
+    match method {
+        "get_state" => {}
+        "pause" => {
+            if !state.is_paused {
+                state.toggle_pause();
+            }
+        }
+        "resume" => {
+            if state.is_paused {
+                state.toggle_pause();
+            }
+        }
+        "skip" => {
+            // A finished session was already counted when it ran out
+            if !state.finish_announced {
+                record_work_session(state, context.cli, context.history_path);
+            }
+            *running = advance(state, context.cli, context.stash_path, context.schedule);
+        }
+        "extend" => {
+            let minutes = params
+                .get("minutes")
+                .and_then(Value::as_i64)
+                .and_then(|minutes| i32::try_from(minutes).ok())
+                .ok_or((INVALID_PARAMS, "extend expects an integer \"minutes\""))?;
+            state.adjust_minutes(minutes);
+        }
+        "set_label" => match params.get("label") {
+            Some(Value::String(label)) if !label.is_empty() => state.label = Some(label.clone()),
+            Some(Value::String(_)) | Some(Value::Null) | None => state.label = None,
+            Some(_) => return Err((INVALID_PARAMS, "set_label expects a string \"label\"")),
+        },
+        _ => return Err((METHOD_NOT_FOUND, "Method not found")),
+    }
+    Ok(snapshot(state))
+}
+
+fn snapshot(state: &TimerState) -> Value {
+    let mut snapshot = state.status_json();
+    snapshot["label"] = json!(state.label);
+    snapshot
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TimerPhase;
+    use clap::Parser;
+
+    fn stash_path() -> PathBuf {
+        std::env::temp_dir().join(format!("pomo-rpc-stash-test-{}.json", std::process::id()))
+    }
+
+    fn history_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "pomo-rpc-history-test-{}.jsonl",
+            std::process::id()
+        ))
+    }
+
+    fn call_with(cli: &Cli, state: &mut TimerState, request: &str) -> Value {
+        let (stash_path, history_path) = (stash_path(), history_path());
+        let context = RequestContext {
+            cli,
+            schedule: None,
+            stash_path: &stash_path,
+            history_path: &history_path,
+        };
+        handle_request(state, &context, request).0
+    }
+
+    fn call(state: &mut TimerState, request: &str) -> Value {
+        call_with(&Cli::try_parse_from(["pomo"]).unwrap(), state, request)
+    }
+
+    #[test]
+    fn test_get_state() {
+        let mut state = TimerState::default();
+        let response = call(
+            &mut state,
+            r#"{"jsonrpc":"2.0","id":1,"method":"get_state"}"#,
+        );
+
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["phase"], "Work");
+        assert_eq!(response["result"]["remaining_seconds"], 25 * 60);
+        assert_eq!(response["result"]["formatted"], "25:00");
+        assert_eq!(response["result"]["is_paused"], false);
+        // The line written back, as shown in the README
+        assert_eq!(
+            response.to_string(),
+            r#"{"id":1,"jsonrpc":"2.0","result":{"formatted":"25:00","is_paused":false,"label":null,"phase":"Work","remaining_seconds":1500}}"#
+        );
+    }
+
+    #[test]
+    fn test_pause_resume_skip_extend() {
+        let mut state = TimerState::default();
+
+        call(&mut state, r#"{"jsonrpc":"2.0","id":1,"method":"pause"}"#);
+        assert!(state.is_paused);
+        // Pausing twice doesn't toggle back
+        call(&mut state, r#"{"jsonrpc":"2.0","id":2,"method":"pause"}"#);
+        assert!(state.is_paused);
+        call(&mut state, r#"{"jsonrpc":"2.0","id":3,"method":"resume"}"#);
+        assert!(!state.is_paused);
+
+        let response = call(&mut state, r#"{"jsonrpc":"2.0","id":4,"method":"skip"}"#);
+        assert_eq!(state.phase, TimerPhase::Break);
+        assert_eq!(response["result"]["phase"], "Break");
+
+        call(
+            &mut state,
+            r#"{"jsonrpc":"2.0","id":5,"method":"extend","params":{"minutes":2}}"#,
+        );
+        assert_eq!(state.remaining_seconds, TimerState::break_duration() + 120);
+    }
+
+    #[test]
+    fn test_set_label() {
+        let mut state = TimerState::default();
+
+        let response = call(
+            &mut state,
+            r#"{"jsonrpc":"2.0","id":1,"method":"set_label","params":{"label":"write report"}}"#,
+        );
+        assert_eq!(state.label.as_deref(), Some("write report"));
+        assert_eq!(response["result"]["label"], "write report");
+
+        call(
+            &mut state,
+            r#"{"jsonrpc":"2.0","id":2,"method":"set_label","params":{"label":null}}"#,
+        );
+        assert!(state.label.is_none());
+    }
+
+    #[test]
+    fn test_commit_rejects_skip_and_extend_during_work() {
+        let cli = Cli::try_parse_from(["pomo", "--commit"]).unwrap();
+        let mut state = TimerState::default();
+        let skip = r#"{"jsonrpc":"2.0","id":1,"method":"skip"}"#;
+        let extend = r#"{"jsonrpc":"2.0","id":2,"method":"extend","params":{"minutes":5}}"#;
+
+        let response = call_with(&cli, &mut state, skip);
+        assert_eq!(response["error"]["code"], COMMITTED);
+        let response = call_with(&cli, &mut state, extend);
+        assert_eq!(response["error"]["code"], COMMITTED);
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.remaining_seconds, 25 * 60);
+
+        // Breaks remain adjustable
+        state.reset_to_break();
+        let response = call_with(&cli, &mut state, extend);
+        assert!(response.get("error").is_none());
+        assert_eq!(state.remaining_seconds, TimerState::break_duration() + 300);
+    }
+
+    #[test]
+    fn test_skip_follows_schedule_and_sync() {
+        let dir = std::env::temp_dir().join(format!("pomo-rpc-schedule-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("day.txt");
+        fs::write(&file, "work 50 Reading\nbreak 10\n").unwrap();
+        let schedule = Schedule::load(&file).unwrap();
+        let cli = Cli::try_parse_from(["pomo"]).unwrap();
+        let (stash_path, history_path) = (stash_path(), history_path());
+        let context = RequestContext {
+            cli: &cli,
+            schedule: Some(&schedule),
+            stash_path: &stash_path,
+            history_path: &history_path,
+        };
+        let skip = r#"{"jsonrpc":"2.0","id":1,"method":"skip"}"#;
+
+        let mut state = TimerState::default();
+        schedule.resume(&mut state);
+        let (_, running) = handle_request(&mut state, &context, skip);
+        assert!(running);
+        assert_eq!(state.phase, TimerPhase::Break);
+        assert_eq!(state.remaining_seconds, 10 * 60);
+        // Skipping the last block ends the run
+        let (response, running) = handle_request(&mut state, &context, skip);
+        assert!(!running);
+        assert!(response.get("error").is_none());
+        assert!(state.schedule.is_none());
+
+        let cli = Cli::try_parse_from(["pomo", "--sync", "0"]).unwrap();
+        let response = call_with(&cli, &mut TimerState::default(), skip);
+        assert_eq!(response["error"]["code"], SYNCED);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut state = TimerState::default();
+
+        let response = call(&mut state, "not json");
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+        let response = call(&mut state, r#"{"jsonrpc":"2.0","id":7,"method":"explode"}"#);
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        let response = call(
+            &mut state,
+            r#"{"jsonrpc":"2.0","id":8,"method":"extend","params":{"minutes":"lots"}}"#,
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(state.remaining_seconds, 25 * 60);
+    }
+}
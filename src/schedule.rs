@@ -0,0 +1,236 @@
+//! Fixed day schedules run with `pomo schedule <file>`.
+//!
+//! A schedule file lists one block per line as `<work|break> <minutes> [label]`.
+//! Blank lines and lines starting with `#` are ignored:
+//!
+//! ```text
+//! # Exam prep
+//! work 50 Linear algebra
+//! break 10
+//! work 50 Physics
+//! ```
+
+use crate::{TimerPhase, TimerState, MAX_PHASE_MINUTES};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Block {
+    pub phase: TimerPhase,
+    pub seconds: u32,
+    pub label: Option<String>,
+}
+
+/// Which schedule is running and how far through it we are, persisted in
+/// the timer state so a restart picks up at the same block.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ScheduleProgress {
+    pub file: PathBuf,
+    pub block: usize,
+}
+
+pub struct Schedule {
+    file: PathBuf,
+    blocks: Vec<Block>,
+}
+
+impl Schedule {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read schedule {}", path.display()))?;
+        let blocks =
+            parse(&contents).with_context(|| format!("invalid schedule {}", path.display()))?;
+        let file = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        Ok(Self { file, blocks })
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Continues a run of this schedule saved in `state`, or starts it from
+    /// the first block.
+    pub fn resume(&self, state: &mut TimerState) {
+        let in_progress = state
+            .schedule
+            .as_ref()
+            .is_some_and(|progress| progress.file == self.file && progress.block < self.len());
+        if !in_progress {
+            self.start_block(state, 0);
+        }
+    }
+
+    /// Moves on to the next block. Returns false once the schedule is done.
+    pub fn advance(&self, state: &mut TimerState) -> bool {
+        let next = state
+            .schedule
+            .as_ref()
+            .map_or(0, |progress| progress.block + 1);
+        if next < self.len() {
+            self.start_block(state, next);
+            true
+        } else {
+            state.schedule = None;
+            false
+        }
+    }
+
+    /// Starts the current block over from its full length.
+    pub fn restart_block(&self, state: &mut TimerState) {
+        let index = state.schedule.as_ref().map_or(0, |progress| progress.block);
+        let is_paused = state.is_paused;
+        self.start_block(state, index.min(self.len() - 1));
+        state.is_paused = is_paused;
+    }
+
+    fn start_block(&self, state: &mut TimerState, index: usize) {
+        let block = &self.blocks[index];
+        state.phase = block.phase;
+        state.start_countdown(block.seconds);
+        state.is_paused = false;
+        state.last_update = None;
+        state.label = block.label.clone();
+        state.schedule = Some(ScheduleProgress {
+            file: self.file.clone(),
+            block: index,
+        });
+    }
+}
+
+fn parse(contents: &str) -> Result<Vec<Block>> {
+    let mut blocks = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let block = parse_block(line).with_context(|| format!("line {}", index + 1))?;
+        blocks.push(block);
+    }
+    if blocks.is_empty() {
+        bail!("schedule has no blocks");
+    }
+    Ok(blocks)
+}
+
+fn parse_block(line: &str) -> Result<Block> {
+    let mut parts = line.splitn(3, char::is_whitespace);
+    let phase = match parts.next().unwrap_or_default() {
+        "work" => TimerPhase::Work,
+        "break" => TimerPhase::Break,
+        other => bail!("unknown block type '{}', expected 'work' or 'break'", other),
+    };
+    let minutes = parts.next().unwrap_or_default();
+    let minutes: u32 = match minutes.parse() {
+        Ok(minutes) if minutes > MAX_PHASE_MINUTES => bail!(
+            "duration '{}' is longer than {} minutes",
+            minutes,
+            MAX_PHASE_MINUTES
+        ),
+        Ok(minutes) if minutes > 0 => minutes,
+        _ => bail!("invalid duration '{}', expected whole minutes", minutes),
+    };
+    let label = parts
+        .next()
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string);
+    Ok(Block {
+        phase,
+        seconds: minutes * 60,
+        label,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(contents: &str) -> Schedule {
+        Schedule {
+            file: PathBuf::from("/tmp/day.txt"),
+            blocks: parse(contents).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let blocks = parse("# Exam prep\n\nwork 50 Linear algebra\n  break 10\nwork 25\n").unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                Block {
+                    phase: TimerPhase::Work,
+                    seconds: 50 * 60,
+                    label: Some("Linear algebra".to_string()),
+                },
+                Block {
+                    phase: TimerPhase::Break,
+                    seconds: 10 * 60,
+                    label: None,
+                },
+                Block {
+                    phase: TimerPhase::Work,
+                    seconds: 25 * 60,
+                    label: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_schedule_errors() {
+        let error = |contents: &str| format!("{:#}", parse(contents).unwrap_err());
+
+        assert!(error("work 25\nnap 20\n").contains("line 2: unknown block type 'nap'"));
+        assert!(error("work soon\n").contains("invalid duration 'soon'"));
+        assert!(error("break 0\n").contains("invalid duration '0'"));
+        assert!(error("work\n").contains("invalid duration ''"));
+        assert!(error("work 99999999\n").contains("longer than 240 minutes"));
+        assert!(error("# nothing here\n").contains("no blocks"));
+    }
+
+    #[test]
+    fn test_schedule_advancement() {
+        let schedule = schedule("work 50 Reading\nbreak 10\n");
+        let mut state = TimerState::default();
+
+        schedule.resume(&mut state);
+        assert_eq!(state.phase, TimerPhase::Work);
+        assert_eq!(state.remaining_seconds, 50 * 60);
+        assert_eq!(state.label.as_deref(), Some("Reading"));
+        assert_eq!(state.schedule.as_ref().unwrap().block, 0);
+
+        assert!(schedule.advance(&mut state));
+        assert_eq!(state.phase, TimerPhase::Break);
+        assert_eq!(state.remaining_seconds, 10 * 60);
+        assert!(state.label.is_none());
+
+        // Resuming mid-schedule keeps the current block and its time
+        state.remaining_seconds = 42;
+        schedule.resume(&mut state);
+        assert_eq!(state.schedule.as_ref().unwrap().block, 1);
+        assert_eq!(state.remaining_seconds, 42);
+
+        // Restarting goes back to the full block length
+        schedule.restart_block(&mut state);
+        assert_eq!(state.schedule.as_ref().unwrap().block, 1);
+        assert_eq!(state.remaining_seconds, 10 * 60);
+
+        // Running past the last block ends the schedule
+        assert!(!schedule.advance(&mut state));
+        assert!(state.schedule.is_none());
+
+        // A different schedule file starts from its first block
+        state.schedule = Some(ScheduleProgress {
+            file: PathBuf::from("/tmp/other.txt"),
+            block: 1,
+        });
+        schedule.resume(&mut state);
+        assert_eq!(state.schedule.as_ref().unwrap().block, 0);
+    }
+}
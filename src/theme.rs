@@ -0,0 +1,75 @@
+//! Colors for the timer screen. The phase picks the color of the time and
+//! the status line; everything is left plain when `NO_COLOR` is set.
+
+use crate::TimerPhase;
+use crossterm::style::{ContentStyle, Stylize};
+
+pub struct Theme {
+    enabled: bool,
+}
+
+impl Theme {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Colors unless `NO_COLOR` is set to a non-empty value (see no-color.org).
+    pub fn from_env() -> Self {
+        Self::new(std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()))
+    }
+
+    /// Style for the time and status line: a gray while paused, otherwise a
+    /// color per phase.
+    pub fn phase(&self, phase: TimerPhase, is_paused: bool) -> ContentStyle {
+        if !self.enabled {
+            return ContentStyle::new();
+        }
+        if is_paused {
+            return ContentStyle::new().dark_grey();
+        }
+        match phase {
+            TimerPhase::Work => ContentStyle::new().red(),
+            TimerPhase::Break => ContentStyle::new().green(),
+            TimerPhase::LongBreak => ContentStyle::new().cyan(),
+            TimerPhase::Interruption => ContentStyle::new().yellow(),
+        }
+    }
+
+    /// Style for lines that should fade into the background, like the
+    /// progress bar while paused.
+    pub fn dimmed(&self) -> ContentStyle {
+        if self.enabled {
+            ContentStyle::new().dim()
+        } else {
+            ContentStyle::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Color;
+
+    #[test]
+    fn test_phase_colors() {
+        let theme = Theme::new(true);
+        assert_eq!(
+            theme.phase(TimerPhase::Work, false).foreground_color,
+            Some(Color::Red)
+        );
+        assert_eq!(
+            theme.phase(TimerPhase::Break, false).foreground_color,
+            Some(Color::Green)
+        );
+        assert_eq!(
+            theme.phase(TimerPhase::Work, true).foreground_color,
+            Some(Color::DarkGrey)
+        );
+
+        // NO_COLOR turns off every style, dimming included
+        let plain = Theme::new(false);
+        assert_eq!(plain.phase(TimerPhase::Work, false), ContentStyle::new());
+        assert_eq!(plain.dimmed(), ContentStyle::new());
+    }
+}